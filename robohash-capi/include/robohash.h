@@ -0,0 +1,130 @@
+#ifndef ROBOHASH_H
+#define ROBOHASH_H
+
+/* Generated with cbindgen from src/lib.rs, run `BLESS=1 cargo test -p robohash-capi --test capi` to update. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define ROBOHASH_FORMAT_PNG 0
+
+#define ROBOHASH_FORMAT_JPEG 1
+
+#define ROBOHASH_MASK_NONE 0
+
+#define ROBOHASH_MASK_CIRCLE 1
+
+#define ROBOHASH_MASK_ROUNDED_RECTANGLE 2
+
+#define ROBOHASH_MASK_SQUIRCLE 3
+
+#define ROBOHASH_MASK_HEXAGON 4
+
+#define ROBOHASH_MASK_CUSTOM 5
+
+#define ROBOHASH_FILL_NONE 0
+
+#define ROBOHASH_FILL_SOLID 1
+
+#define ROBOHASH_FILL_LINEAR_GRADIENT 2
+
+#define ROBOHASH_FILL_RADIAL_GRADIENT 3
+
+#define ROBOHASH_FILL_HASH_DERIVED 4
+
+#define ROBOHASH_ALGORITHM_V1 0
+
+#define ROBOHASH_ALGORITHM_V2 1
+
+typedef enum RobohashStatus {
+  ROBOHASH_STATUS_OK = 0,
+  ROBOHASH_STATUS_NULL_ARGUMENT = 1,
+  ROBOHASH_STATUS_INVALID_UTF8 = 2,
+  ROBOHASH_STATUS_RENDER_FAILED = 3,
+  ROBOHASH_STATUS_PANICKED = 4,
+  ROBOHASH_STATUS_INVALID_FORMAT = 5,
+  ROBOHASH_STATUS_INVALID_OPTION = 6,
+} RobohashStatus;
+
+typedef struct RobohashGenerator RobohashGenerator;
+
+/**
+ * `format`, `mask`, `fill` and `algorithm` take the matching `ROBOHASH_*` constants, other values
+ * fail with `ROBOHASH_STATUS_INVALID_FORMAT` or `ROBOHASH_STATUS_INVALID_OPTION`. A `fill` other
+ * than `ROBOHASH_FILL_NONE` replaces `background_set`, and a non-null `seed` is hashed instead of
+ * the text. `mask_path` is relative to the working directory and may not contain `..` or be
+ * absolute.
+ */
+typedef struct RobohashOptions {
+  const char *set;
+  const char *colour;
+  const char *background_set;
+  uint32_t width;
+  uint32_t height;
+  uint32_t format;
+  uint8_t quality;
+  uint32_t mask;
+  uint32_t mask_radius;
+  const char *mask_path;
+  uint32_t mask_border;
+  uint32_t fill;
+  uint8_t fill_from[4];
+  uint8_t fill_to[4];
+  float fill_angle;
+  uint32_t algorithm;
+  const uint64_t *seed;
+} RobohashOptions;
+
+typedef struct RobohashBuffer {
+  uint8_t *data;
+  size_t len;
+} RobohashBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+struct RobohashOptions robohash_options_default(void);
+
+/**
+ * # Safety
+ * `sets_root` and `backgrounds_root` must be null or valid NUL-terminated strings.
+ */
+struct RobohashGenerator *robohash_generator_new(const char *sets_root,
+                                                 const char *backgrounds_root);
+
+/**
+ * # Safety
+ * `generator` must be null or a pointer returned by `robohash_generator_new` that has not been
+ * freed yet.
+ */
+void robohash_generator_free(struct RobohashGenerator *generator);
+
+/**
+ * # Safety
+ * `generator` must come from `robohash_generator_new`, `text` must be a valid NUL-terminated
+ * string, `options` must be null or point to valid options and `out` must point to writable
+ * memory for a buffer. The strings in `options` must be null or NUL-terminated and `seed` must
+ * be null or point to a readable `uint64_t`. On success the buffer must be released with `robohash_buffer_free`.
+ */
+enum RobohashStatus robohash_render(const struct RobohashGenerator *generator,
+                                    const char *text,
+                                    const struct RobohashOptions *options,
+                                    struct RobohashBuffer *out);
+
+/**
+ * # Safety
+ * `buffer` must have been filled by `robohash_render` and not freed yet.
+ */
+void robohash_buffer_free(struct RobohashBuffer buffer);
+
+const char *robohash_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ROBOHASH_H */
@@ -0,0 +1,557 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use robohash::error::Error;
+use robohash::{Algorithm, Background, Format, Mask, RenderOptions, Rgba, RoboHashGenerator};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobohashStatus {
+    Ok = 0,
+    NullArgument = 1,
+    InvalidUtf8 = 2,
+    RenderFailed = 3,
+    Panicked = 4,
+    InvalidFormat = 5,
+    InvalidOption = 6,
+}
+
+pub const ROBOHASH_FORMAT_PNG: u32 = 0;
+pub const ROBOHASH_FORMAT_JPEG: u32 = 1;
+
+pub const ROBOHASH_MASK_NONE: u32 = 0;
+pub const ROBOHASH_MASK_CIRCLE: u32 = 1;
+pub const ROBOHASH_MASK_ROUNDED_RECTANGLE: u32 = 2;
+pub const ROBOHASH_MASK_SQUIRCLE: u32 = 3;
+pub const ROBOHASH_MASK_HEXAGON: u32 = 4;
+pub const ROBOHASH_MASK_CUSTOM: u32 = 5;
+
+pub const ROBOHASH_FILL_NONE: u32 = 0;
+pub const ROBOHASH_FILL_SOLID: u32 = 1;
+pub const ROBOHASH_FILL_LINEAR_GRADIENT: u32 = 2;
+pub const ROBOHASH_FILL_RADIAL_GRADIENT: u32 = 3;
+pub const ROBOHASH_FILL_HASH_DERIVED: u32 = 4;
+
+pub const ROBOHASH_ALGORITHM_V1: u32 = 0;
+pub const ROBOHASH_ALGORITHM_V2: u32 = 1;
+
+/// `format`, `mask`, `fill` and `algorithm` take the matching `ROBOHASH_*` constants, other values
+/// fail with `ROBOHASH_STATUS_INVALID_FORMAT` or `ROBOHASH_STATUS_INVALID_OPTION`. A `fill` other
+/// than `ROBOHASH_FILL_NONE` replaces `background_set`, and a non-null `seed` is hashed instead of
+/// the text. `mask_path` is relative to the working directory and may not contain `..` or be
+/// absolute.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RobohashOptions {
+    pub set: *const c_char,
+    pub colour: *const c_char,
+    pub background_set: *const c_char,
+    pub width: u32,
+    pub height: u32,
+    pub format: u32,
+    pub quality: u8,
+    pub mask: u32,
+    pub mask_radius: u32,
+    pub mask_path: *const c_char,
+    pub mask_border: u32,
+    pub fill: u32,
+    pub fill_from: [u8; 4],
+    pub fill_to: [u8; 4],
+    pub fill_angle: f32,
+    pub algorithm: u32,
+    pub seed: *const u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct RobohashBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+#[derive(Debug)]
+pub struct RobohashGenerator {
+    generator: RoboHashGenerator,
+    options: RenderOptions,
+}
+
+enum Failure {
+    NullArgument(&'static str),
+    InvalidUtf8(&'static str),
+    InvalidFormat(u32),
+    InvalidOption(&'static str, u32),
+    Render(Error),
+}
+
+impl Failure {
+    fn status(&self) -> RobohashStatus {
+        match self {
+            Failure::NullArgument(_) => RobohashStatus::NullArgument,
+            Failure::InvalidUtf8(_) => RobohashStatus::InvalidUtf8,
+            Failure::InvalidFormat(_) => RobohashStatus::InvalidFormat,
+            Failure::InvalidOption(_, _) => RobohashStatus::InvalidOption,
+            Failure::Render(_) => RobohashStatus::RenderFailed,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Failure::NullArgument(name) => format!("{name} must not be null"),
+            Failure::InvalidUtf8(name) => format!("{name} is not valid UTF-8"),
+            Failure::InvalidFormat(format) => format!("format {format} is not supported"),
+            Failure::InvalidOption(name, value) => format!("{name} {value} is not supported"),
+            Failure::Render(e) => render_error_message(e),
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn robohash_options_default() -> RobohashOptions {
+    RobohashOptions {
+        set: ptr::null(),
+        colour: ptr::null(),
+        background_set: ptr::null(),
+        width: 0,
+        height: 0,
+        format: ROBOHASH_FORMAT_PNG,
+        quality: 90,
+        mask: ROBOHASH_MASK_NONE,
+        mask_radius: 0,
+        mask_path: ptr::null(),
+        mask_border: 0,
+        fill: ROBOHASH_FILL_NONE,
+        fill_from: [0, 0, 0, 0],
+        fill_to: [0, 0, 0, 0],
+        fill_angle: 0.0,
+        algorithm: ROBOHASH_ALGORITHM_V1,
+        seed: ptr::null(),
+    }
+}
+
+/// # Safety
+/// `sets_root` and `backgrounds_root` must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn robohash_generator_new(
+    sets_root: *const c_char,
+    backgrounds_root: *const c_char,
+) -> *mut RobohashGenerator {
+    let result = panic::catch_unwind(|| generator(sets_root, backgrounds_root));
+    match result {
+        Ok(Ok(generator)) => {
+            clear_last_error();
+            Box::into_raw(Box::new(generator))
+        }
+        Ok(Err(failure)) => {
+            set_last_error(failure.message());
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_last_error(String::from("creating the generator panicked"));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `generator` must be null or a pointer returned by `robohash_generator_new` that has not been
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn robohash_generator_free(generator: *mut RobohashGenerator) {
+    if !generator.is_null() {
+        drop(Box::from_raw(generator));
+    }
+}
+
+/// # Safety
+/// `generator` must come from `robohash_generator_new`, `text` must be a valid NUL-terminated
+/// string, `options` must be null or point to valid options and `out` must point to writable
+/// memory for a buffer. The strings in `options` must be null or NUL-terminated and `seed` must
+/// be null or point to a readable `uint64_t`. On success the buffer must be released with `robohash_buffer_free`.
+#[no_mangle]
+pub unsafe extern "C" fn robohash_render(
+    generator: *const RobohashGenerator,
+    text: *const c_char,
+    options: *const RobohashOptions,
+    out: *mut RobohashBuffer,
+) -> RobohashStatus {
+    if out.is_null() {
+        set_last_error(Failure::NullArgument("out").message());
+        return RobohashStatus::NullArgument;
+    }
+    *out = RobohashBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| render(generator, text, options)));
+    match result {
+        Ok(Ok(bytes)) => {
+            clear_last_error();
+            let len = bytes.len();
+            let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+            *out = RobohashBuffer { data, len };
+            RobohashStatus::Ok
+        }
+        Ok(Err(failure)) => {
+            set_last_error(failure.message());
+            failure.status()
+        }
+        Err(_) => {
+            set_last_error(String::from("rendering panicked"));
+            RobohashStatus::Panicked
+        }
+    }
+}
+
+/// # Safety
+/// `buffer` must have been filled by `robohash_render` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn robohash_buffer_free(buffer: RobohashBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn robohash_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+unsafe fn generator(
+    sets_root: *const c_char,
+    backgrounds_root: *const c_char,
+) -> Result<RobohashGenerator, Failure> {
+    let mut options = RenderOptions::new();
+    if let Some(sets_root) = optional_str(sets_root, "sets_root")? {
+        options = options.with_set_location(sets_root);
+    }
+    if let Some(backgrounds_root) = optional_str(backgrounds_root, "backgrounds_root")? {
+        options = options.with_background_location(backgrounds_root);
+    }
+    Ok(RobohashGenerator {
+        generator: RoboHashGenerator::new(),
+        options,
+    })
+}
+
+unsafe fn render(
+    generator: *const RobohashGenerator,
+    text: *const c_char,
+    options: *const RobohashOptions,
+) -> Result<Vec<u8>, Failure> {
+    let generator = generator
+        .as_ref()
+        .ok_or(Failure::NullArgument("generator"))?;
+    let text = optional_str(text, "text")?.ok_or(Failure::NullArgument("text"))?;
+    let options = match options.as_ref() {
+        Some(options) => *options,
+        None => robohash_options_default(),
+    };
+    let mut render_options = generator.options.clone();
+    if let Some(set) = optional_str(options.set, "set")? {
+        render_options = render_options.with_set(set);
+    }
+    if let Some(colour) = optional_str(options.colour, "colour")? {
+        render_options = render_options.with_colour(colour);
+    }
+    if let Some(background_set) = optional_str(options.background_set, "background_set")? {
+        render_options = render_options.with_background_set(background_set);
+    }
+    if options.width > 0 && options.height > 0 {
+        render_options = render_options.with_size(options.width, options.height);
+    }
+    render_options = render_options.with_format(match options.format {
+        ROBOHASH_FORMAT_PNG => Format::Png,
+        ROBOHASH_FORMAT_JPEG => Format::Jpeg {
+            quality: options.quality,
+        },
+        format => return Err(Failure::InvalidFormat(format)),
+    });
+    if let Some(mask) = mask(&options)? {
+        render_options = render_options.with_mask(mask);
+    }
+    if options.mask_border > 0 {
+        render_options = render_options.with_mask_border(options.mask_border);
+    }
+    if let Some(fill) = fill(&options)? {
+        render_options = render_options.with_background(fill);
+    }
+    render_options = render_options.with_algorithm(match options.algorithm {
+        ROBOHASH_ALGORITHM_V1 => Algorithm::V1,
+        ROBOHASH_ALGORITHM_V2 => Algorithm::V2,
+        algorithm => return Err(Failure::InvalidOption("algorithm", algorithm)),
+    });
+    if let Some(seed) = options.seed.as_ref() {
+        render_options = render_options.with_seed(*seed);
+    }
+    generator
+        .generator
+        .render_bytes(text, &render_options)
+        .map_err(Failure::Render)
+}
+
+unsafe fn mask(options: &RobohashOptions) -> Result<Option<Mask>, Failure> {
+    let mask = match options.mask {
+        ROBOHASH_MASK_NONE => return Ok(None),
+        ROBOHASH_MASK_CIRCLE => Mask::Circle,
+        ROBOHASH_MASK_ROUNDED_RECTANGLE => Mask::RoundedRectangle {
+            radius: options.mask_radius,
+        },
+        ROBOHASH_MASK_SQUIRCLE => Mask::Squircle,
+        ROBOHASH_MASK_HEXAGON => Mask::Hexagon,
+        ROBOHASH_MASK_CUSTOM => {
+            let path = optional_str(options.mask_path, "mask_path")?
+                .ok_or(Failure::NullArgument("mask_path"))?;
+            Mask::Custom(String::from(path))
+        }
+        mask => return Err(Failure::InvalidOption("mask", mask)),
+    };
+    Ok(Some(mask))
+}
+
+fn fill(options: &RobohashOptions) -> Result<Option<Background>, Failure> {
+    let (from, to) = (Rgba(options.fill_from), Rgba(options.fill_to));
+    let fill = match options.fill {
+        ROBOHASH_FILL_NONE => return Ok(None),
+        ROBOHASH_FILL_SOLID => Background::Solid(from),
+        ROBOHASH_FILL_LINEAR_GRADIENT => Background::LinearGradient {
+            from,
+            to,
+            angle: options.fill_angle,
+        },
+        ROBOHASH_FILL_RADIAL_GRADIENT => Background::RadialGradient {
+            inner: from,
+            outer: to,
+        },
+        ROBOHASH_FILL_HASH_DERIVED => Background::HashDerived,
+        fill => return Err(Failure::InvalidOption("fill", fill)),
+    };
+    Ok(Some(fill))
+}
+
+unsafe fn optional_str<'a>(
+    string: *const c_char,
+    name: &'static str,
+) -> Result<Option<&'a str>, Failure> {
+    if string.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map(Some)
+        .map_err(|_| Failure::InvalidUtf8(name))
+}
+
+fn render_error_message(error: &Error) -> String {
+    match error {
+        Error::IoError(e) => format!("{error}: {e}"),
+        Error::ImageProcessingError(e) => format!("{error}: {e}"),
+        _ => error.to_string(),
+    }
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETS_ROOT: &str = "../sets";
+    const BACKGROUNDS_ROOT: &str = "../backgrounds";
+
+    fn generator() -> *mut RobohashGenerator {
+        let sets_root = CString::new(SETS_ROOT).unwrap();
+        let backgrounds_root = CString::new(BACKGROUNDS_ROOT).unwrap();
+        unsafe { robohash_generator_new(sets_root.as_ptr(), backgrounds_root.as_ptr()) }
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(robohash_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn robohash_render_returns_the_same_bytes_as_the_builder() {
+        // arrange
+        let generator = generator();
+        let set = CString::new("set4").unwrap();
+        let text = CString::new("test").unwrap();
+        let options = RobohashOptions {
+            set: set.as_ptr(),
+            width: 64,
+            height: 64,
+            ..robohash_options_default()
+        };
+        let mut buffer = RobohashBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let expected = robohash::RoboHashBuilder::new("test")
+            .with_set_location(SETS_ROOT)
+            .with_set("set4")
+            .with_size(64, 64)
+            .build()
+            .unwrap()
+            .assemble()
+            .unwrap();
+        // act
+        let status = unsafe { robohash_render(generator, text.as_ptr(), &options, &mut buffer) };
+        // assert
+        assert_eq!(status, RobohashStatus::Ok);
+        assert_eq!(
+            unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) },
+            expected.as_slice()
+        );
+        unsafe {
+            robohash_buffer_free(buffer);
+            robohash_generator_free(generator);
+        }
+    }
+
+    #[test]
+    fn robohash_render_applies_mask_fill_algorithm_and_seed() {
+        // arrange
+        let generator = generator();
+        let set = CString::new("set4").unwrap();
+        let text = CString::new("test").unwrap();
+        let seed = 42;
+        let options = RobohashOptions {
+            set: set.as_ptr(),
+            width: 64,
+            height: 64,
+            mask: ROBOHASH_MASK_ROUNDED_RECTANGLE,
+            mask_radius: 8,
+            mask_border: 2,
+            fill: ROBOHASH_FILL_LINEAR_GRADIENT,
+            fill_from: [255, 0, 0, 255],
+            fill_to: [0, 0, 255, 255],
+            fill_angle: 45.0,
+            algorithm: ROBOHASH_ALGORITHM_V2,
+            seed: &seed,
+            ..robohash_options_default()
+        };
+        let mut buffer = RobohashBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let expected = robohash::RoboHashBuilder::new("test")
+            .with_set_location(SETS_ROOT)
+            .with_set("set4")
+            .with_size(64, 64)
+            .with_mask(Mask::RoundedRectangle { radius: 8 })
+            .with_mask_border(2)
+            .with_background(Background::LinearGradient {
+                from: Rgba([255, 0, 0, 255]),
+                to: Rgba([0, 0, 255, 255]),
+                angle: 45.0,
+            })
+            .with_algorithm(Algorithm::V2)
+            .with_seed(42)
+            .build()
+            .unwrap()
+            .assemble()
+            .unwrap();
+        // act
+        let status = unsafe { robohash_render(generator, text.as_ptr(), &options, &mut buffer) };
+        // assert
+        assert_eq!(status, RobohashStatus::Ok);
+        assert_eq!(
+            unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) },
+            expected.as_slice()
+        );
+        unsafe {
+            robohash_buffer_free(buffer);
+            robohash_generator_free(generator);
+        }
+    }
+
+    #[test]
+    fn robohash_render_rejects_unknown_enum_values() {
+        // arrange
+        let generator = generator();
+        let text = CString::new("test").unwrap();
+        let render = |options: RobohashOptions| {
+            let mut buffer = RobohashBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            let status =
+                unsafe { robohash_render(generator, text.as_ptr(), &options, &mut buffer) };
+            (status, last_error())
+        };
+        // act
+        let format = render(RobohashOptions {
+            format: 7,
+            ..robohash_options_default()
+        });
+        let mask = render(RobohashOptions {
+            mask: 9,
+            ..robohash_options_default()
+        });
+        let fill = render(RobohashOptions {
+            fill: u32::MAX,
+            ..robohash_options_default()
+        });
+        let algorithm = render(RobohashOptions {
+            algorithm: 2,
+            ..robohash_options_default()
+        });
+        // assert
+        assert_eq!(
+            format,
+            (
+                RobohashStatus::InvalidFormat,
+                String::from("format 7 is not supported")
+            )
+        );
+        assert_eq!(mask.0, RobohashStatus::InvalidOption);
+        assert_eq!(fill.0, RobohashStatus::InvalidOption);
+        assert_eq!(algorithm.0, RobohashStatus::InvalidOption);
+        unsafe { robohash_generator_free(generator) };
+    }
+
+    #[test]
+    fn robohash_render_reports_a_status_and_message_on_failure() {
+        // arrange
+        let generator = generator();
+        let set = CString::new("missing").unwrap();
+        let text = CString::new("test").unwrap();
+        let options = RobohashOptions {
+            set: set.as_ptr(),
+            ..robohash_options_default()
+        };
+        let mut buffer = RobohashBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        // act
+        let status = unsafe { robohash_render(generator, text.as_ptr(), &options, &mut buffer) };
+        let null_text = unsafe { robohash_render(generator, ptr::null(), &options, &mut buffer) };
+        let null_text_error = last_error();
+        // assert
+        assert_eq!(status, RobohashStatus::RenderFailed);
+        assert!(buffer.data.is_null());
+        assert_eq!(null_text, RobohashStatus::NullArgument);
+        assert_eq!(null_text_error, "text must not be null");
+        unsafe { robohash_generator_free(generator) };
+    }
+}
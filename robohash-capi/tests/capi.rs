@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const HEADER: &str = "include/robohash.h";
+const BLESS: &str = "BLESS";
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn library_dir() -> PathBuf {
+    let executable = std::env::current_exe().unwrap();
+    executable.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn header_matches_the_c_api() {
+    // arrange
+    let config = cbindgen::Config::from_file(manifest_dir().join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    // act
+    cbindgen::Builder::new()
+        .with_crate(manifest_dir())
+        .with_config(config)
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+    // assert
+    let header = manifest_dir().join(HEADER);
+    if std::env::var(BLESS).is_ok_and(|bless| bless == "1") {
+        std::fs::write(&header, &generated).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&header).unwrap();
+    assert_eq!(
+        String::from_utf8(generated).unwrap(),
+        expected,
+        "{HEADER} is out of date, run with {BLESS}=1 to regenerate it"
+    )
+}
+
+#[test]
+fn c_program_renders_through_the_c_api() {
+    // arrange
+    let executable = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capi_test");
+    let library_dir = library_dir();
+    let compiled = Command::new(std::env::var("CC").unwrap_or_else(|_| String::from("cc")))
+        .arg(manifest_dir().join("tests/capi/main.c"))
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .arg("-L")
+        .arg(&library_dir)
+        .arg("-lrobohash_capi")
+        .arg("-o")
+        .arg(&executable)
+        .status()
+        .unwrap();
+    assert!(compiled.success());
+    // act
+    let output = Command::new(&executable)
+        .current_dir(manifest_dir().parent().unwrap())
+        .env("LD_LIBRARY_PATH", &library_dir)
+        .env("DYLD_LIBRARY_PATH", &library_dir)
+        .output()
+        .unwrap();
+    // assert
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
@@ -0,0 +1,66 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "robohash.h"
+
+#define CHECK(condition)                                                   \
+    do {                                                                   \
+        if (!(condition)) {                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                           \
+            return 1;                                                      \
+        }                                                                  \
+    } while (0)
+
+int main(void) {
+    static const unsigned char png_signature[] = {0x89, 'P', 'N', 'G'};
+
+    RobohashGenerator *generator = robohash_generator_new("./sets", "./backgrounds");
+    CHECK(generator != NULL);
+
+    RobohashOptions options = robohash_options_default();
+    options.set = "set4";
+    options.background_set = "bg1";
+    options.width = 64;
+    options.height = 64;
+
+    RobohashBuffer buffer;
+    CHECK(robohash_render(generator, "test", &options, &buffer) == ROBOHASH_STATUS_OK);
+    CHECK(buffer.len > sizeof(png_signature));
+    CHECK(memcmp(buffer.data, png_signature, sizeof(png_signature)) == 0);
+    robohash_buffer_free(buffer);
+
+    options.format = ROBOHASH_FORMAT_JPEG;
+    options.quality = 80;
+    CHECK(robohash_render(generator, "test", &options, &buffer) == ROBOHASH_STATUS_OK);
+    CHECK(buffer.data[0] == 0xFF && buffer.data[1] == 0xD8);
+    robohash_buffer_free(buffer);
+
+    options.format = ROBOHASH_FORMAT_PNG;
+    options.mask = ROBOHASH_MASK_CIRCLE;
+    options.fill = ROBOHASH_FILL_SOLID;
+    options.fill_from[0] = 255;
+    options.fill_from[3] = 255;
+    options.algorithm = ROBOHASH_ALGORITHM_V2;
+    CHECK(robohash_render(generator, "test", &options, &buffer) == ROBOHASH_STATUS_OK);
+    CHECK(memcmp(buffer.data, png_signature, sizeof(png_signature)) == 0);
+    robohash_buffer_free(buffer);
+
+    options.format = 7;
+    CHECK(robohash_render(generator, "test", &options, &buffer) == ROBOHASH_STATUS_INVALID_FORMAT);
+    options.format = ROBOHASH_FORMAT_PNG;
+    options.mask = 42;
+    CHECK(robohash_render(generator, "test", &options, &buffer) == ROBOHASH_STATUS_INVALID_OPTION);
+    options.mask = ROBOHASH_MASK_NONE;
+
+    options.set = "missing";
+    CHECK(robohash_render(generator, "test", &options, &buffer) == ROBOHASH_STATUS_RENDER_FAILED);
+    CHECK(buffer.data == NULL);
+    CHECK(robohash_last_error() != NULL);
+    fprintf(stderr, "expected error: %s\n", robohash_last_error());
+
+    CHECK(robohash_render(generator, NULL, &options, &buffer) == ROBOHASH_STATUS_NULL_ARGUMENT);
+
+    robohash_generator_free(generator);
+    return 0;
+}
@@ -0,0 +1,364 @@
+use std::path::Path;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use robohash::error::Error;
+use robohash::{AssetSource, FileSystemAssetSource, Format, RenderOptions, RoboHashBuilder};
+
+const SET_DEFAULT: &str = "set1";
+const EXTENSIONS: [&str; 7] = [".png", ".gif", ".jpg", ".bmp", ".jpeg", ".ppm", ".datauri"];
+const JPEG_QUALITY: u8 = 75;
+// Each hash array entry is a block of the 128 digit hexdigest and has to fit an i64, so blocks
+// can be at most 15 digits long.
+const MIN_HASHCOUNT: usize = 9;
+const MAX_HASHCOUNT: usize = 128;
+
+fn py_error(error: Error) -> PyErr {
+    PyRuntimeError::new_err(error.to_string())
+}
+
+#[pyclass(name = "Robohash", module = "robohash")]
+#[derive(Debug, Clone)]
+pub struct Robohash {
+    #[pyo3(get)]
+    hexdigest: String,
+    #[pyo3(get)]
+    hasharray: Vec<i64>,
+    #[pyo3(get)]
+    format: String,
+    sets_root: String,
+    backgrounds_root: String,
+    data: Option<Vec<u8>>,
+}
+
+#[pymethods]
+impl Robohash {
+    #[new]
+    #[pyo3(signature = (string, hashcount = 11, ignoreext = true, sets_root = "./sets", backgrounds_root = "./backgrounds"))]
+    fn new(
+        string: &str,
+        hashcount: usize,
+        ignoreext: bool,
+        sets_root: &str,
+        backgrounds_root: &str,
+    ) -> PyResult<Self> {
+        if !(MIN_HASHCOUNT..=MAX_HASHCOUNT).contains(&hashcount) {
+            return Err(PyValueError::new_err(format!(
+                "hashcount must be between {MIN_HASHCOUNT} and {MAX_HASHCOUNT}"
+            )));
+        }
+        let mut format = String::from("png");
+        let mut string = string;
+        if ignoreext {
+            if let Some(extension) = EXTENSIONS
+                .iter()
+                .find(|extension| string.to_lowercase().ends_with(*extension))
+            {
+                if matches!(*extension, ".jpg" | ".jpeg") {
+                    format = String::from("jpeg");
+                }
+                string = &string[..string.len() - extension.len()];
+            }
+        }
+        let hexdigest = robohash::sha512_digest(string).map_err(py_error)?;
+        let hasharray = robohash::split_hash(&hexdigest, hashcount).map_err(py_error)?;
+        Ok(Self {
+            hexdigest,
+            hasharray,
+            format,
+            sets_root: String::from(sets_root),
+            backgrounds_root: String::from(backgrounds_root),
+            data: None,
+        })
+    }
+
+    #[getter]
+    fn sets(&self) -> PyResult<Vec<String>> {
+        directories(Path::new(&self.sets_root))
+    }
+
+    #[getter]
+    fn bgsets(&self) -> PyResult<Vec<String>> {
+        directories(Path::new(&self.backgrounds_root))
+    }
+
+    #[getter]
+    fn colors(&self) -> PyResult<Vec<String>> {
+        directories(&Path::new(&self.sets_root).join(SET_DEFAULT))
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        self.data.as_ref().map(|data| PyBytes::new(py, data))
+    }
+
+    #[getter]
+    fn img(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let Some(data) = &self.data else {
+            return Ok(None);
+        };
+        let buffer = py
+            .import("io")?
+            .call_method1("BytesIO", (PyBytes::new(py, data),))?;
+        let image = py.import("PIL.Image")?.call_method1("open", (buffer,))?;
+        Ok(Some(image.unbind()))
+    }
+
+    #[pyo3(signature = (roboset = None, color = None, format = None, bgset = None, sizex = 300, sizey = 300))]
+    #[allow(clippy::too_many_arguments)]
+    fn assemble(
+        &mut self,
+        py: Python<'_>,
+        roboset: Option<&str>,
+        color: Option<&str>,
+        format: Option<&str>,
+        bgset: Option<&str>,
+        sizex: u32,
+        sizey: u32,
+    ) -> PyResult<()> {
+        let (options, format) = self.render_options(roboset, color, format, bgset, sizex, sizey)?;
+        let robo_hash = RoboHashBuilder::new("")
+            .with_options(options)
+            .build()
+            .map_err(py_error)?;
+        self.data = Some(
+            py.allow_threads(|| robo_hash.assemble())
+                .map_err(py_error)?,
+        );
+        self.format = format;
+        Ok(())
+    }
+}
+
+impl Robohash {
+    fn render_options(
+        &self,
+        roboset: Option<&str>,
+        color: Option<&str>,
+        format: Option<&str>,
+        bgset: Option<&str>,
+        sizex: u32,
+        sizey: u32,
+    ) -> PyResult<(RenderOptions, String)> {
+        let mut options = RenderOptions::new()
+            .with_set_location(&self.sets_root)
+            .with_background_location(&self.backgrounds_root)
+            .with_hash_array(&self.hasharray)
+            .with_size(sizex, sizey);
+
+        let sets = self.sets()?;
+        let roboset = match roboset {
+            Some("any") => self.pick(&sets, 1),
+            Some(roboset) if sets.iter().any(|set| set == roboset) => Some(roboset),
+            _ => sets.first().map(String::as_str),
+        };
+        let Some(roboset) = roboset else {
+            return Err(PyValueError::new_err(format!(
+                "no sets found in {}",
+                self.sets_root
+            )));
+        };
+        options = options.with_set(roboset);
+        if roboset == SET_DEFAULT {
+            let colors = self.colors()?;
+            if let Some(color) = color.filter(|color| colors.iter().any(|known| known == color)) {
+                options = options.with_colour(color);
+            }
+        }
+
+        let bgsets = self.bgsets().unwrap_or_default();
+        let bgset = match bgset {
+            Some("any") => self.pick(&bgsets, 2),
+            Some(bgset) if bgsets.iter().any(|set| set == bgset) => Some(bgset),
+            _ => None,
+        };
+        if let Some(bgset) = bgset {
+            options = options.with_background_set(bgset);
+        }
+
+        let format = format.unwrap_or(&self.format).to_lowercase();
+        options = options.with_format(match format.as_str() {
+            "png" => Format::Png,
+            "jpeg" | "jpg" => Format::Jpeg {
+                quality: JPEG_QUALITY,
+            },
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unsupported format {format}"
+                )))
+            }
+        });
+        Ok((options, format))
+    }
+
+    fn pick<'a>(&self, list: &'a [String], hash_index: usize) -> Option<&'a str> {
+        let value = *self.hasharray.get(hash_index)?;
+        if list.is_empty() {
+            return None;
+        }
+        Some(list[value.rem_euclid(list.len() as i64) as usize].as_str())
+    }
+}
+
+fn directories(path: &Path) -> PyResult<Vec<String>> {
+    Ok(FileSystemAssetSource
+        .list(path)
+        .map_err(py_error)?
+        .into_iter()
+        .filter(|entry| !entry.starts_with('.') && path.join(entry).is_dir())
+        .collect())
+}
+
+#[pymodule]
+#[pyo3(name = "robohash")]
+fn robohash_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Robohash>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(
+        string: &str,
+        roboset: Option<&str>,
+        color: Option<&str>,
+        bgset: Option<&str>,
+    ) -> (Vec<String>, Option<String>) {
+        let robohash = Robohash::new(string, 11, true, "../sets", "../backgrounds").unwrap();
+        let (options, _) = robohash
+            .render_options(roboset, color, None, bgset, 300, 300)
+            .unwrap();
+        let resolution = RoboHashBuilder::new("")
+            .with_options(options)
+            .build()
+            .unwrap()
+            .resolve()
+            .unwrap();
+        let relative = |path: &str| String::from(path.trim_start_matches("../"));
+        (
+            resolution.parts.iter().map(|part| relative(part)).collect(),
+            resolution.background.as_deref().map(relative),
+        )
+    }
+
+    // Snapshots of this crate's own selection, not renders from the Python package.
+    #[test]
+    fn robohash_selects_stable_parts_for_known_inputs() {
+        // arrange
+        let cases = [
+            (
+                ("test", None, None, None),
+                vec![
+                    "sets/set1/red/003#01Body/002#red_body-08.png",
+                    "sets/set1/red/004#02Face/007#red_face-01.png",
+                    "sets/set1/red/002#Accessory/007#red_accessory-01.png",
+                    "sets/set1/red/001#Eyes/004#red_eyes-04.png",
+                    "sets/set1/red/000#Mouth/003#red_mouth-01.png",
+                ],
+                None,
+            ),
+            (
+                ("robohash", Some("any"), None, Some("any")),
+                vec![
+                    "sets/set2/004#01FaceColors/004#final9.png",
+                    "sets/set2/003#02BodyColors/000#final3.png",
+                    "sets/set2/006#03Faces/007#final4.png",
+                    "sets/set2/000#04Body/007#final6.png",
+                    "sets/set2/002#Eyes/006#final10.png",
+                    "sets/set2/001#Mouth/006#final10.png",
+                    "sets/set2/005#Nose/009#final1.png",
+                ],
+                Some("backgrounds/bg2/004#robotBG-01.png"),
+            ),
+            (
+                ("avatar", Some("set3"), None, Some("bg2")),
+                vec![
+                    "sets/set3/005#01BaseFace/035#Robot-Design51.png",
+                    "sets/set3/001#02Wave/000#wave1.png",
+                    "sets/set3/006#03Antenna/003#Robot-Design5.png",
+                    "sets/set3/003#04Eyes/004#Robot-Design2.png",
+                    "sets/set3/002#05Eyebrows/002#Robot-Design1.png",
+                    "sets/set3/004#06Nose/004#Robot-Design2.png",
+                    "sets/set3/000#07Mouth/008#Robot-Design4.png",
+                ],
+                Some("backgrounds/bg2/005#robotBG-02.png"),
+            ),
+            (
+                ("user@example.com", Some("set1"), Some("green"), Some("any")),
+                vec![
+                    "sets/set1/green/003#01Body/000#green_body-08.png",
+                    "sets/set1/green/004#02Face/001#green_face-09.png",
+                    "sets/set1/green/002#Accessory/009#green_accessory-09.png",
+                    "sets/set1/green/001#Eyes/002#green_eyes-02.png",
+                    "sets/set1/green/000#Mouth/001#green_mouth-04.png",
+                ],
+                Some("backgrounds/bg2/005#robotBG-02.png"),
+            ),
+            (
+                ("42", Some("set1"), Some("purple"), None),
+                vec![
+                    "sets/set1/purple/003#01Body/007#purple_body-06.png",
+                    "sets/set1/purple/004#02Face/005#purple_face-03.png",
+                    "sets/set1/purple/002#Accessory/001#purple_accessory-02.png",
+                    "sets/set1/purple/001#Eyes/003#purple_eyes-10.png",
+                    "sets/set1/purple/000#Mouth/008#purple_mouth-08.png",
+                ],
+                None,
+            ),
+        ];
+        for ((string, roboset, color, bgset), parts, background) in cases {
+            // act
+            let (actual_parts, actual_background) = selection(string, roboset, color, bgset);
+            // assert
+            assert_eq!(actual_parts, parts, "{string}");
+            assert_eq!(actual_background.as_deref(), background, "{string}")
+        }
+    }
+
+    #[test]
+    fn robohash_falls_back_to_the_defaults_for_unknown_choices() {
+        // act
+        let unknown = selection("test", Some("set9"), Some("teal"), Some("bg9"));
+        // assert
+        assert_eq!(unknown, selection("test", None, None, None))
+    }
+
+    #[test]
+    fn robohash_strips_image_extensions_and_keeps_the_format() {
+        // act
+        let robohash = Robohash::new("test.JPG", 11, true, "../sets", "../backgrounds").unwrap();
+        let kept = Robohash::new("test.jpg", 11, false, "../sets", "../backgrounds").unwrap();
+        // assert
+        assert_eq!(robohash.format, "jpeg");
+        assert_eq!(robohash.hexdigest, robohash::sha512_digest("test").unwrap());
+        assert_eq!(kept.format, "png");
+        assert_eq!(kept.hexdigest, robohash::sha512_digest("test.jpg").unwrap())
+    }
+
+    #[test]
+    fn robohash_renders_png_for_extensions_without_an_encoder() {
+        for string in ["test.gif", "test.bmp", "test.ppm", "test.datauri"] {
+            // act
+            let robohash = Robohash::new(string, 11, true, "../sets", "../backgrounds").unwrap();
+            // assert
+            assert_eq!(robohash.format, "png", "{string}");
+            assert_eq!(robohash.hexdigest, robohash::sha512_digest("test").unwrap())
+        }
+    }
+
+    #[test]
+    fn robohash_accepts_only_hashcounts_that_fit_the_hash_array() {
+        // arrange
+        let robohash =
+            |hashcount| Robohash::new("test", hashcount, true, "../sets", "../backgrounds").is_ok();
+        // act
+        let accepted = (MIN_HASHCOUNT..=MAX_HASHCOUNT).all(robohash);
+        let rejected = [0, 1, MIN_HASHCOUNT - 1, MAX_HASHCOUNT + 1].map(robohash);
+        // assert
+        assert!(accepted);
+        assert_eq!(rejected, [false; 4])
+    }
+}
@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::Arc;
+
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use robohash::error::Error;
+use robohash::{Format, MemoryAssetSource, RenderOptions, RoboHashBuilder};
+
+thread_local! {
+    static ASSETS: RefCell<Arc<MemoryAssetSource>> = RefCell::default();
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    options: RenderOptions,
+}
+
+#[wasm_bindgen]
+impl Options {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[wasm_bindgen(js_name = withSet)]
+    pub fn with_set(self, set: &str) -> Self {
+        Self {
+            options: self.options.with_set(set),
+        }
+    }
+
+    #[wasm_bindgen(js_name = withColour)]
+    pub fn with_colour(self, colour: &str) -> Self {
+        Self {
+            options: self.options.with_colour(colour),
+        }
+    }
+
+    #[wasm_bindgen(js_name = withBackgroundSet)]
+    pub fn with_background_set(self, background_set: &str) -> Self {
+        Self {
+            options: self.options.with_background_set(background_set),
+        }
+    }
+
+    #[wasm_bindgen(js_name = withSize)]
+    pub fn with_size(self, width: u32, height: u32) -> Self {
+        Self {
+            options: self.options.with_size(width, height),
+        }
+    }
+
+    #[wasm_bindgen(js_name = withJpeg)]
+    pub fn with_jpeg(self, quality: u8) -> Self {
+        Self {
+            options: self.options.with_format(Format::Jpeg { quality }),
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = addAsset)]
+pub fn add_asset(path: &str, bytes: &[u8]) {
+    ASSETS.with(|assets| {
+        Arc::make_mut(&mut assets.borrow_mut()).insert_file(Path::new(path), bytes.to_vec())
+    });
+}
+
+#[wasm_bindgen]
+pub fn generate(text: &str, options: &Options) -> Result<Uint8Array, JsError> {
+    let bytes = render(text, options)?;
+    Ok(Uint8Array::from(bytes.as_slice()))
+}
+
+fn render(text: &str, options: &Options) -> Result<Vec<u8>, Error> {
+    let assets = ASSETS.with(|assets| Arc::clone(&assets.borrow()));
+    RoboHashBuilder::new(text)
+        .with_options(options.options.clone().with_asset_source(assets))
+        .build()?
+        .assemble()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn add_set() {
+        for (file, colour) in [
+            ("sets/custom/000#a/000#a0.png", [255, 0, 0, 255]),
+            ("sets/custom/001#b/000#b0.png", [0, 0, 0, 0]),
+        ] {
+            let mut part = Cursor::new(Vec::new());
+            RgbaImage::from_pixel(4, 4, Rgba(colour))
+                .write_to(&mut part, ImageOutputFormat::Png)
+                .unwrap();
+            add_asset(file, part.get_ref());
+        }
+    }
+
+    #[test]
+    fn render_uses_the_added_assets() {
+        // arrange
+        add_set();
+        let options = Options::new().with_set("custom").with_size(8, 8);
+        // act
+        let bytes = render("test", &options).unwrap();
+        // assert
+        let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (8, 8));
+        assert_eq!(*image.get_pixel(4, 4), Rgba([255, 0, 0, 255]))
+    }
+
+    #[test]
+    fn render_fails_for_a_set_that_was_not_added() {
+        // arrange
+        let options = Options::new().with_set("missing");
+        // act
+        let bytes = render("test", &options);
+        // assert
+        assert!(bytes.is_err())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    fn generate_returns_the_encoded_image() {
+        // arrange
+        add_set();
+        let options = Options::new().with_set("custom").with_size(8, 8);
+        // act
+        let bytes = generate("test", &options).unwrap();
+        // assert
+        assert_eq!(bytes.to_vec(), render("test", &options).unwrap())
+    }
+}
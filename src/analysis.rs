@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+use crate::set_definition::SetDefinition;
+use crate::{materials, Background, RenderOptions, Resolution, RoboHashBuilder, SET_DEFAULT};
+
+pub fn combinations(options: &RenderOptions) -> Result<u128, Error> {
+    let source = options.asset_source.as_ref();
+    let definition =
+        SetDefinition::load(source, options.symlinks, &options.set_root, &options.set)?;
+    let mut combinations = 0_u128;
+    for set in set_variants(options, &definition)? {
+        let category_sizes = match &definition {
+            Some(definition) => definition
+                .categories
+                .iter()
+                .map(|category| {
+                    let files = materials::files_in_category(
+                        source,
+                        &options.set_root,
+                        &set,
+                        &category.directory,
+                    )?;
+                    Ok(match category.presence.unwrap_or(1.0) {
+                        presence if presence <= 0.0 => 1,
+                        presence if presence < 1.0 => files.len() + 1,
+                        _ => files.len(),
+                    })
+                })
+                .collect::<Result<Vec<usize>, Error>>()?,
+            None => materials::category_directories(source, &options.set_root, &set)?
+                .iter()
+                .map(|category| {
+                    materials::files_in_category(source, &options.set_root, &set, category)
+                        .map(|files| files.len())
+                })
+                .collect::<Result<Vec<usize>, Error>>()?,
+        };
+        let set_combinations = category_sizes.iter().fold(1_u128, |product, size| {
+            product.saturating_mul(*size as u128)
+        });
+        combinations = combinations.saturating_add(set_combinations);
+    }
+    if let Background::Set(background_set) = &options.background {
+        let backgrounds =
+            materials::categories_in_set(source, &options.background_root, background_set)?;
+        combinations = combinations.saturating_mul(backgrounds.len() as u128);
+    }
+    Ok(combinations)
+}
+
+pub fn collision_probability(combinations: u128, users: u64) -> f64 {
+    if combinations == 0 {
+        return if users > 1 { 1.0 } else { 0.0 };
+    }
+    let users = users as f64;
+    let pairs = users * (users - 1.0).max(0.0) / 2.0;
+    -(-pairs / combinations as f64).exp_m1()
+}
+
+pub fn collision_groups(
+    inputs: &[&str],
+    options: &RenderOptions,
+) -> Result<Vec<Vec<String>>, Error> {
+    let mut groups: BTreeMap<Resolution, Vec<String>> = BTreeMap::new();
+    for input in inputs {
+        let resolution = RoboHashBuilder::new(input)
+            .with_options(options.clone())
+            .build()?
+            .resolve()?;
+        groups
+            .entry(resolution)
+            .or_default()
+            .push(String::from(*input));
+    }
+    Ok(groups
+        .into_values()
+        .filter(|inputs| inputs.len() > 1)
+        .collect())
+}
+
+fn set_variants(
+    options: &RenderOptions,
+    definition: &Option<SetDefinition>,
+) -> Result<Vec<String>, Error> {
+    let colours = match (definition, &options.colour) {
+        (Some(definition), Some(colour)) => {
+            definition.check_colour(&options.set, colour)?;
+            vec![colour.to_owned()]
+        }
+        (Some(definition), None) => definition.colours.to_owned(),
+        (None, Some(colour)) if options.set == SET_DEFAULT => vec![colour.to_owned()],
+        (None, None) if options.set == SET_DEFAULT => materials::category_directories(
+            options.asset_source.as_ref(),
+            &options.set_root,
+            SET_DEFAULT,
+        )?,
+        (None, _) => Vec::new(),
+    };
+    if colours.is_empty() {
+        return Ok(vec![options.set.to_owned()]);
+    }
+    Ok(colours
+        .iter()
+        .map(|colour| format!("{}/{}", options.set, colour))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::asset::MemoryAssetSource;
+
+    fn assets() -> MemoryAssetSource {
+        let mut assets = MemoryAssetSource::new();
+        for file in [
+            "./sets/custom/000#a/000#a0.png",
+            "./sets/custom/000#a/001#a1.png",
+            "./sets/custom/001#b/000#b0.png",
+            "./sets/custom/001#b/001#b1.png",
+            "./sets/custom/001#b/002#b2.png",
+            "./sets/single/000#a/000#a0.png",
+            "./backgrounds/bg/000#bg0.png",
+            "./backgrounds/bg/001#bg1.png",
+        ] {
+            assets.insert_file(Path::new(file), vec![]);
+        }
+        assets
+    }
+
+    #[test]
+    fn combinations_multiplies_category_and_background_sizes() {
+        // arrange
+        let options = RenderOptions::new()
+            .with_asset_source(assets())
+            .with_set("custom");
+        // act
+        let without_background = combinations(&options);
+        let with_background = combinations(&options.with_background_set("bg"));
+        // assert
+        assert_eq!(without_background.unwrap(), 6);
+        assert_eq!(with_background.unwrap(), 12)
+    }
+
+    #[test]
+    fn combinations_skips_files_next_to_the_categories() {
+        // arrange
+        let mut assets = assets();
+        assets.insert_file(Path::new("./sets/custom/README.md"), vec![]);
+        let options = RenderOptions::new()
+            .with_asset_source(assets)
+            .with_set("custom");
+        // act
+        let combinations = combinations(&options);
+        // assert
+        assert_eq!(combinations.unwrap(), 6)
+    }
+
+    #[test]
+    fn combinations_counts_an_omitted_part_for_optional_categories() {
+        // arrange
+        let mut assets = assets();
+        let definition = "[[categories]]\ndirectory = \"000#a\"\npresence = 0.5\n\
+            [[categories]]\ndirectory = \"001#b\"\n\
+            [[categories]]\ndirectory = \"002#c\"\npresence = 0.0";
+        assets.insert_file(Path::new("./sets/custom/set.toml"), definition.into());
+        assets.insert_file(Path::new("./sets/custom/002#c/000#c0.png"), vec![]);
+        let options = RenderOptions::new()
+            .with_asset_source(assets)
+            .with_set("custom");
+        // act
+        let combinations = combinations(&options);
+        // assert
+        assert_eq!(combinations.unwrap(), (2 + 1) * 3)
+    }
+
+    #[test]
+    fn combinations_counts_every_colour_of_set1() {
+        // arrange
+        let options = RenderOptions::new().with_set("set1");
+        let colours =
+            materials::categories_in_set(&crate::asset::FileSystemAssetSource, "./sets", "set1")
+                .unwrap();
+        // act
+        let all_colours = combinations(&options).unwrap();
+        // assert
+        let per_colour = colours
+            .iter()
+            .map(|colour| combinations(&options.clone().with_colour(colour)).unwrap())
+            .sum::<u128>();
+        assert_eq!(all_colours, per_colour)
+    }
+
+    #[test]
+    fn collision_probability_follows_the_birthday_bound() {
+        // act
+        let single_user = collision_probability(365, 1);
+        let birthday = collision_probability(365, 23);
+        let crowded = collision_probability(6, 100);
+        // assert
+        assert_eq!(single_user, 0.0);
+        assert!((birthday - 0.5).abs() < 0.02);
+        assert!(crowded > 0.999)
+    }
+
+    #[test]
+    fn collision_groups_reports_inputs_sharing_the_same_selection() {
+        // arrange
+        let options = RenderOptions::new()
+            .with_asset_source(assets())
+            .with_set("single");
+        // act
+        let groups = collision_groups(&["a", "b", "c"], &options);
+        // assert
+        assert_eq!(groups.unwrap(), vec![vec!["a", "b", "c"]])
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+pub trait AssetSource: Debug + Send + Sync {
+    fn list(&self, directory: &Path) -> Result<Vec<String>, Error>;
+    fn read(&self, file: &Path) -> Result<Vec<u8>, Error>;
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.list(path).is_ok()
+    }
+
+    fn is_symlink(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        Ok(path.to_path_buf())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SymlinkPolicy {
+    Deny,
+    #[default]
+    WithinRoot,
+    Follow,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystemAssetSource;
+
+pub(crate) fn default_asset_source() -> Arc<dyn AssetSource> {
+    static DEFAULT: OnceLock<Arc<dyn AssetSource>> = OnceLock::new();
+    Arc::clone(DEFAULT.get_or_init(|| Arc::new(FileSystemAssetSource)))
+}
+
+// Sources are told apart by their allocation, so clones of one `Arc` share an identity and every
+// default source is the same one.
+pub(crate) fn source_identity(source: &Arc<dyn AssetSource>) -> usize {
+    Arc::as_ptr(source) as *const () as usize
+}
+
+impl AssetSource for FileSystemAssetSource {
+    fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+        let mut entries = Vec::new();
+        for entry in directory.read_dir()? {
+            if let Ok(entry) = entry?.file_name().into_string() {
+                entries.push(entry);
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+        Ok(std::fs::read(file)?)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        Ok(path.canonicalize()?)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAssetSource {
+    directories: BTreeMap<PathBuf, BTreeSet<String>>,
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryAssetSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_file(&mut self, file: &Path, bytes: Vec<u8>) {
+        let file = normalise(file);
+        self.register_ancestors(&file);
+        self.files.insert(file, bytes);
+    }
+
+    pub fn insert_directory(&mut self, directory: &Path, entries: Vec<String>) {
+        let directory = normalise(directory);
+        self.register_ancestors(&directory);
+        self.directories
+            .entry(directory)
+            .or_default()
+            .extend(entries);
+    }
+
+    fn register_ancestors(&mut self, path: &Path) {
+        let mut child = path;
+        while let (Some(parent), Some(name)) = (child.parent(), child.file_name()) {
+            self.directories
+                .entry(parent.to_path_buf())
+                .or_default()
+                .insert(name.to_string_lossy().into_owned());
+            child = parent;
+        }
+    }
+}
+
+impl AssetSource for MemoryAssetSource {
+    fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+        match self.directories.get(&normalise(directory)) {
+            Some(entries) => Ok(entries.iter().cloned().collect()),
+            None => Err(not_found(directory)),
+        }
+    }
+
+    fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+        match self.files.get(&normalise(file)) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => Err(not_found(file)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedAssetSource {
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+impl EmbeddedAssetSource {
+    pub const fn new(files: &'static [(&'static str, &'static [u8])]) -> Self {
+        Self { files }
+    }
+}
+
+impl AssetSource for EmbeddedAssetSource {
+    fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+        let directory = normalise(directory);
+        let entries = self
+            .files
+            .iter()
+            .filter_map(|(file, _)| {
+                let file = normalise(Path::new(file));
+                let name = file.strip_prefix(&directory).ok()?.components().next()?;
+                Some(name.as_os_str().to_string_lossy().into_owned())
+            })
+            .collect::<BTreeSet<String>>();
+        if entries.is_empty() {
+            return Err(not_found(&directory));
+        }
+        Ok(entries.into_iter().collect())
+    }
+
+    fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+        let path = normalise(file);
+        match self
+            .files
+            .iter()
+            .find(|(file, _)| normalise(Path::new(file)) == path)
+        {
+            Some((_, bytes)) => Ok(bytes.to_vec()),
+            None => Err(not_found(file)),
+        }
+    }
+}
+
+impl<S: AssetSource + ?Sized> AssetSource for Arc<S> {
+    fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+        self.as_ref().list(directory)
+    }
+
+    fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+        self.as_ref().read(file)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.as_ref().is_dir(path)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.as_ref().is_symlink(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        self.as_ref().canonicalize(path)
+    }
+}
+
+fn normalise(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::IoError(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{path:#?} not found"),
+    ))
+}
+
+#[cfg(feature = "async")]
+pub trait AsyncAssetSource: Send + Sync {
+    fn list(
+        &self,
+        directory: &Path,
+    ) -> impl std::future::Future<Output = Result<Vec<String>, Error>> + Send;
+    fn read(&self, file: &Path)
+        -> impl std::future::Future<Output = Result<Vec<u8>, Error>> + Send;
+
+    fn is_dir(&self, path: &Path) -> impl std::future::Future<Output = bool> + Send {
+        async { self.list(path).await.is_ok() }
+    }
+
+    fn is_symlink(&self, _path: &Path) -> impl std::future::Future<Output = bool> + Send {
+        async { false }
+    }
+
+    fn canonicalize(
+        &self,
+        path: &Path,
+    ) -> impl std::future::Future<Output = Result<PathBuf, Error>> + Send {
+        let path = path.to_path_buf();
+        async { Ok(path) }
+    }
+}
+
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioFileSystemAssetSource;
+
+#[cfg(feature = "async")]
+impl AsyncAssetSource for TokioFileSystemAssetSource {
+    async fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+        let mut read_dir = tokio::fs::read_dir(directory).await?;
+        let mut entries = Vec::new();
+        while let Some(entry) = read_dir.next_entry().await? {
+            if let Ok(entry) = entry.file_name().into_string() {
+                entries.push(entry);
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    async fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+        Ok(tokio::fs::read(file).await?)
+    }
+
+    async fn is_dir(&self, path: &Path) -> bool {
+        tokio::fs::metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    async fn is_symlink(&self, path: &Path) -> bool {
+        tokio::fs::symlink_metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_symlink())
+    }
+
+    async fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        Ok(tokio::fs::canonicalize(path).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_system_asset_source_lists_sorted_entries() {
+        // arrange
+        let source = FileSystemAssetSource;
+        // act
+        let entries = source.list(Path::new("./sets/set4"));
+        // assert
+        assert_eq!(
+            entries.unwrap(),
+            vec![
+                "000#00body",
+                "001#01fur",
+                "002#02eyes",
+                "003#03mouth",
+                "004#04accessories"
+            ]
+        )
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_system_asset_source_skips_entries_that_are_not_utf8() {
+        // arrange
+        use std::os::unix::ffi::OsStrExt;
+        let directory = std::env::temp_dir().join(format!("robohash-utf8-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("000#a.png"), [0]).unwrap();
+        std::fs::write(
+            directory.join(std::ffi::OsStr::from_bytes(b"001#\xff.png")),
+            [0],
+        )
+        .unwrap();
+        // act
+        let entries = FileSystemAssetSource.list(&directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+        // assert
+        assert_eq!(entries.unwrap(), vec!["000#a.png"])
+    }
+
+    #[test]
+    fn memory_asset_source_lists_directories_created_by_inserted_files() {
+        // arrange
+        let mut source = MemoryAssetSource::new();
+        source.insert_file(Path::new("./sets/a/001#b/002#c.png"), vec![2]);
+        source.insert_file(Path::new("sets/a/000#a/001#a.png"), vec![1]);
+        // act
+        let categories = source.list(Path::new("sets/a"));
+        let file = source.read(Path::new("./sets/a/000#a/001#a.png"));
+        // assert
+        assert_eq!(categories.unwrap(), vec!["000#a", "001#b"]);
+        assert_eq!(file.unwrap(), vec![1])
+    }
+
+    #[test]
+    fn embedded_asset_source_lists_and_reads_static_files() {
+        // arrange
+        static FILES: &[(&str, &[u8])] = &[
+            ("sets/a/001#b/002#c.png", &[2]),
+            ("./sets/a/000#a/001#a.png", &[1]),
+        ];
+        let source = EmbeddedAssetSource::new(FILES);
+        // act
+        let categories = source.list(Path::new("./sets/a"));
+        let file = source.read(Path::new("sets/a/000#a/001#a.png"));
+        let missing = source.list(Path::new("./sets/b"));
+        // assert
+        assert_eq!(categories.unwrap(), vec!["000#a", "001#b"]);
+        assert_eq!(file.unwrap(), vec![1]);
+        assert!(matches!(missing, Err(Error::IoError(_))))
+    }
+
+    #[test]
+    fn memory_asset_source_returns_io_error_for_missing_entries() {
+        // arrange
+        let source = MemoryAssetSource::new();
+        // act
+        let entries = source.list(Path::new("./sets"));
+        // assert
+        assert!(matches!(entries, Err(Error::IoError(_))))
+    }
+}
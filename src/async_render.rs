@@ -0,0 +1,386 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use tokio::sync::Semaphore;
+
+use crate::asset::{AsyncAssetSource, MemoryAssetSource, SymlinkPolicy};
+use crate::error::Error;
+use crate::materials::{self, Links};
+use crate::{Background, RoboHash, RoboHashBuilder};
+
+static DEFAULT_LIMIT: OnceLock<Semaphore> = OnceLock::new();
+
+fn default_limit() -> &'static Semaphore {
+    DEFAULT_LIMIT.get_or_init(|| {
+        let permits = std::thread::available_parallelism()
+            .map(|permits| permits.get())
+            .unwrap_or(4);
+        Semaphore::new(permits)
+    })
+}
+
+impl RoboHashBuilder {
+    pub async fn build_async<S: AsyncAssetSource>(&self, source: &S) -> Result<RoboHash, Error> {
+        materials::check_name(&self.options.set)?;
+        let mut assets = MemoryAssetSource::new();
+        let sets_root = Path::new(&self.options.set_root);
+        assets.insert_directory(sets_root, source.list(sets_root).await?);
+        let set_directory = sets_root.join(&self.options.set);
+        match source.list(&set_directory).await {
+            Ok(entries) => {
+                insert_directories(source, &mut assets, &set_directory, &entries).await?;
+                assets.insert_directory(&set_directory, entries);
+            }
+            Err(e) if e.is_not_found() => {}
+            Err(e) => return Err(e),
+        }
+        for definition in ["set.toml", "set.json"] {
+            let file = set_directory.join(definition);
+            let symlinks = self.options.symlinks;
+            match confine(
+                source,
+                symlinks,
+                &self.options.set_root,
+                &file.to_string_lossy(),
+            )
+            .await
+            {
+                Err(e) if e.is_not_found() => continue,
+                confined => confined?,
+            }
+            match source.read(&file).await {
+                Ok(bytes) => assets.insert_file(&file, bytes),
+                Err(e) if e.is_not_found() => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let mut robo_hash = self.clone().with_asset_source(assets).build()?;
+        robo_hash.asset_source = Arc::clone(&self.options.asset_source);
+        Ok(robo_hash)
+    }
+}
+
+impl RoboHash {
+    pub async fn assemble_async<S: AsyncAssetSource>(&self, source: &S) -> Result<String, Error> {
+        self.assemble_async_limited(source, default_limit()).await
+    }
+
+    pub async fn assemble_async_limited<S: AsyncAssetSource>(
+        &self,
+        source: &S,
+        limit: &Semaphore,
+    ) -> Result<String, Error> {
+        let _permit = match limit.acquire().await {
+            Ok(permit) => permit,
+            Err(e) => return Err(Error::RenderTaskFailed(format!("{e}"))),
+        };
+        let assets = self.prefetch(source).await?;
+        let mut robo_hash = self.clone();
+        robo_hash.asset_source = Arc::new(assets);
+        match tokio::task::spawn_blocking(move || robo_hash.assemble_base64()).await {
+            Ok(base64) => base64,
+            Err(e) => Err(Error::RenderTaskFailed(format!("{e}"))),
+        }
+    }
+
+    async fn prefetch<S: AsyncAssetSource>(&self, source: &S) -> Result<MemoryAssetSource, Error> {
+        if self.is_missing_required_data() {
+            return Err(Error::RoboHashMissingRequiredData);
+        }
+
+        materials::check_name(&self.set)?;
+        if let Background::Set(set) = &self.background {
+            materials::check_name(set)?;
+        }
+
+        let mut assets = MemoryAssetSource::new();
+        let set_directory = Path::new(&self.sets_root).join(&self.set);
+        let categories = source.list(&set_directory).await?;
+        insert_directories(source, &mut assets, &set_directory, &categories).await?;
+        assets.insert_directory(&set_directory, categories);
+        if let Background::Set(set) = &self.background {
+            let background_directory = Path::new(&self.background_root).join(set);
+            let backgrounds = source.list(&background_directory).await?;
+            assets.insert_directory(&background_directory, backgrounds);
+        }
+
+        let resolution = self.resolve_in(&assets)?;
+        let mut files = resolution
+            .parts
+            .into_iter()
+            .map(|part| (self.sets_root.as_str(), part))
+            .collect::<Vec<(&str, String)>>();
+        if let Some(background) = resolution.background {
+            files.push((&self.background_root, background));
+        }
+        if let Some(mask) = self.mask_file()? {
+            files.push((&self.mask_root, mask));
+        }
+        for (root, file) in files {
+            confine(source, self.symlinks, root, &file).await?;
+            let file = Path::new(&file);
+            match source.read(file).await {
+                Ok(bytes) => assets.insert_file(file, bytes),
+                Err(e) => return Err(Error::ImageOpenFailed(format!("{e:#?}"))),
+            }
+        }
+        Ok(assets)
+    }
+}
+
+async fn insert_directories<S: AsyncAssetSource>(
+    source: &S,
+    assets: &mut MemoryAssetSource,
+    parent: &Path,
+    entries: &[String],
+) -> Result<(), Error> {
+    for entry in entries {
+        let directory = parent.join(entry);
+        if source.is_dir(&directory).await {
+            assets.insert_directory(&directory, source.list(&directory).await?);
+        }
+    }
+    Ok(())
+}
+
+async fn confine<S: AsyncAssetSource>(
+    source: &S,
+    policy: SymlinkPolicy,
+    root: &str,
+    file: &str,
+) -> Result<(), Error> {
+    let mut links = ResolvedLinks::default();
+    match policy {
+        SymlinkPolicy::Follow => {}
+        SymlinkPolicy::Deny => {
+            for ancestor in materials::below_root(Path::new(root), Path::new(file)) {
+                if source.is_symlink(ancestor).await {
+                    links.symlinks.insert(ancestor.to_path_buf());
+                }
+            }
+        }
+        SymlinkPolicy::WithinRoot => {
+            for path in [root, file].map(Path::new) {
+                let canonical = source.canonicalize(path).await?;
+                links.canonical.insert(path.to_path_buf(), canonical);
+            }
+        }
+    }
+    materials::confine(&links, policy, root, file)
+}
+
+#[derive(Default)]
+struct ResolvedLinks {
+    symlinks: BTreeSet<PathBuf>,
+    canonical: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl Links for ResolvedLinks {
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.symlinks.contains(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        match self.canonical.get(path) {
+            Some(canonical) => Ok(canonical.to_owned()),
+            None => Err(Error::AssetOutsideRoot(path.to_string_lossy().into_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::TokioFileSystemAssetSource;
+    use crate::{Mask, RoboHashBuilder};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn assemble_async_returns_the_same_image_as_assemble_base64() {
+        // arrange
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .with_background_set("bg1")
+            .with_size(64, 64)
+            .build()
+            .unwrap();
+        let expected = robo_hash.assemble_base64().unwrap();
+        // act
+        let base64 = robo_hash.assemble_async(&TokioFileSystemAssetSource).await;
+        // assert
+        assert_eq!(base64.unwrap(), expected)
+    }
+
+    #[tokio::test]
+    async fn build_async_returns_the_same_robo_hash_as_build() {
+        // arrange
+        let builders = [
+            RoboHashBuilder::new("test"),
+            RoboHashBuilder::new("test").with_set("set2"),
+            RoboHashBuilder::new("test")
+                .with_set("set1")
+                .with_colour("blue"),
+        ];
+        for builder in builders {
+            // act
+            let robo_hash = builder.build_async(&TokioFileSystemAssetSource).await;
+            // assert
+            assert_eq!(robo_hash.unwrap(), builder.build().unwrap())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Unreadable(MemoryAssetSource, &'static str);
+
+    impl AsyncAssetSource for Unreadable {
+        async fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+            match directory.ends_with(self.1) {
+                true => Err(Error::IoError(std::io::ErrorKind::PermissionDenied.into())),
+                false => crate::AssetSource::list(&self.0, directory),
+            }
+        }
+
+        async fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+            match file.ends_with(self.1) {
+                true => Err(Error::IoError(std::io::ErrorKind::PermissionDenied.into())),
+                false => crate::AssetSource::read(&self.0, file),
+            }
+        }
+
+        async fn is_dir(&self, path: &Path) -> bool {
+            path.ends_with(self.1) || crate::AssetSource::is_dir(&self.0, path)
+        }
+    }
+
+    #[tokio::test]
+    async fn build_async_and_assemble_async_return_read_errors() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("./sets/custom/000#a/000#a.png"), vec![]);
+        assets.insert_file(Path::new("./sets/custom/001#b/000#b.png"), vec![]);
+        let mut defined = assets.clone();
+        defined.insert_file(Path::new("./sets/custom/set.toml"), vec![]);
+        let builder = RoboHashBuilder::new("test").with_set("custom");
+        // act
+        let definition = builder.build_async(&Unreadable(defined, "set.toml")).await;
+        let category = builder
+            .build_async(&Unreadable(assets.clone(), "001#b"))
+            .await;
+        let robo_hash = builder.clone().with_asset_source(assets.clone()).build();
+        let render = robo_hash
+            .unwrap()
+            .assemble_async(&Unreadable(assets, "001#b"))
+            .await;
+        // assert
+        assert!(matches!(definition, Err(Error::IoError(_))));
+        assert!(matches!(category, Err(Error::IoError(_))));
+        assert!(matches!(render, Err(Error::IoError(_))))
+    }
+
+    #[tokio::test]
+    async fn assemble_async_limited_waits_for_a_permit() {
+        // arrange
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .with_size(32, 32)
+            .build()
+            .unwrap();
+        let limit = Semaphore::new(1);
+        let held = limit.acquire().await.unwrap();
+        // act
+        let render = robo_hash.assemble_async_limited(&TokioFileSystemAssetSource, &limit);
+        let timed_out = tokio::time::timeout(std::time::Duration::from_millis(50), render).await;
+        drop(held);
+        // assert
+        assert!(timed_out.is_err())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn assemble_async_applies_the_symlink_policy_to_every_read() {
+        // arrange
+        let directory = std::env::temp_dir().join(format!("robohash-async-{}", std::process::id()));
+        let sets_root = directory.join("sets");
+        std::fs::create_dir_all(sets_root.join("linked/000#a")).unwrap();
+        std::fs::create_dir_all(sets_root.join("plain/000#a")).unwrap();
+        let part = crate::image::encode(
+            &image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])),
+            crate::Format::Png,
+        )
+        .unwrap();
+        std::fs::write(directory.join("secret.png"), &part).unwrap();
+        std::fs::write(sets_root.join("plain/000#a/000#a0.png"), &part).unwrap();
+        std::os::unix::fs::symlink(
+            directory.join("secret.png"),
+            sets_root.join("linked/000#a/000#a0.png"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(directory.join("secret.png"), directory.join("mask.png"))
+            .unwrap();
+        let mask = Mask::Custom(String::from("mask.png"));
+        let render = |set, policy, mask: Option<Mask>| {
+            let mut robo_hash = RoboHashBuilder::new("test")
+                .with_set_location(sets_root.to_str().unwrap())
+                .with_mask_location(directory.to_str().unwrap())
+                .with_set(set)
+                .with_size(4, 4)
+                .with_symlink_policy(policy);
+            if let Some(mask) = mask {
+                robo_hash = robo_hash.with_mask(mask);
+            }
+            async move {
+                robo_hash
+                    .build()
+                    .unwrap()
+                    .assemble_async(&TokioFileSystemAssetSource)
+                    .await
+            }
+        };
+        // act
+        let within_root = render("linked", SymlinkPolicy::WithinRoot, None).await;
+        let follow = render("linked", SymlinkPolicy::Follow, None).await;
+        let plain = render("plain", SymlinkPolicy::Deny, None).await;
+        let masked = render("plain", SymlinkPolicy::Deny, Some(mask)).await;
+        std::fs::write(
+            directory.join("set.toml"),
+            "categories = [{ directory = \"000#a\" }]",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(directory.join("set.toml"), sets_root.join("plain/set.toml"))
+            .unwrap();
+        let definition = RoboHashBuilder::new("test")
+            .with_set_location(sets_root.to_str().unwrap())
+            .with_set("plain")
+            .with_symlink_policy(SymlinkPolicy::Deny)
+            .build_async(&TokioFileSystemAssetSource)
+            .await;
+        std::fs::remove_dir_all(&directory).unwrap();
+        // assert
+        assert!(matches!(within_root, Err(Error::AssetOutsideRoot(_))));
+        assert!(follow.is_ok());
+        assert!(plain.is_ok());
+        assert!(matches!(masked, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(definition, Err(Error::AssetOutsideRoot(_))))
+    }
+
+    #[tokio::test]
+    async fn assemble_async_rejects_custom_masks_outside_of_the_mask_root() {
+        // arrange
+        let mut robo_hash = RoboHashBuilder::new("test")
+            .with_size(8, 8)
+            .with_mask_location("./sets")
+            .build()
+            .unwrap();
+        robo_hash.mask = Some(Mask::Custom(String::from("../Cargo.toml")));
+        // act
+        let escaped = robo_hash.assemble_async(&TokioFileSystemAssetSource).await;
+        let built = RoboHashBuilder::new("test")
+            .with_mask(Mask::Custom(String::from("../Cargo.toml")))
+            .build_async(&TokioFileSystemAssetSource)
+            .await;
+        // assert
+        assert!(matches!(escaped, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(built, Err(Error::AssetOutsideRoot(_))))
+    }
+}
@@ -0,0 +1,92 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rgba(pub [u8; 4]);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum Background {
+    #[default]
+    None,
+    Set(String),
+    Solid(Rgba),
+    LinearGradient {
+        from: Rgba,
+        to: Rgba,
+        angle: f32,
+    },
+    RadialGradient {
+        inner: Rgba,
+        outer: Rgba,
+    },
+    HashDerived,
+}
+
+// Angles compare by their bits, like they hash, so backgrounds can be map keys.
+impl PartialEq for Background {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Background::None, Background::None) => true,
+            (Background::HashDerived, Background::HashDerived) => true,
+            (Background::Set(set), Background::Set(other)) => set == other,
+            (Background::Solid(colour), Background::Solid(other)) => colour == other,
+            (
+                Background::LinearGradient { from, to, angle },
+                Background::LinearGradient {
+                    from: other_from,
+                    to: other_to,
+                    angle: other_angle,
+                },
+            ) => {
+                from == other_from
+                    && to == other_to
+                    && angle_bits(*angle) == angle_bits(*other_angle)
+            }
+            (
+                Background::RadialGradient { inner, outer },
+                Background::RadialGradient {
+                    inner: other_inner,
+                    outer: other_outer,
+                },
+            ) => inner == other_inner && outer == other_outer,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Background {}
+
+impl Hash for Background {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Background::None | Background::HashDerived => {}
+            Background::Set(set) => set.hash(state),
+            Background::Solid(colour) => colour.hash(state),
+            Background::LinearGradient { from, to, angle } => {
+                from.hash(state);
+                to.hash(state);
+                angle_bits(*angle).hash(state);
+            }
+            Background::RadialGradient { inner, outer } => {
+                inner.hash(state);
+                outer.hash(state);
+            }
+        }
+    }
+}
+
+fn angle_bits(angle: f32) -> u32 {
+    (angle + 0.0).to_bits()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Backdrop {
+    None,
+    Image(String),
+    Solid(Rgba),
+    LinearGradient { from: Rgba, to: Rgba, angle: f32 },
+    RadialGradient { inner: Rgba, outer: Rgba },
+    HashDerived { hue_seed: i64, offset_seed: i64 },
+}
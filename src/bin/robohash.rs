@@ -0,0 +1,76 @@
+use std::process::ExitCode;
+
+use robohash::{contact_sheet, RoboHashConfig};
+
+const USAGE: &str = "usage: robohash contact-sheet <set> [--config <file>] [--profile <name>] [--sets <directory>] [--size <pixels>] [--output <file>]";
+const ENV_PROFILE: &str = "ROBOHASH_PROFILE";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let result = match args.first().map(String::as_str) {
+        Some("contact-sheet") => run_contact_sheet(&args[1..]),
+        _ => Err(String::from(USAGE)),
+    };
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_contact_sheet(args: &[String]) -> Result<(), String> {
+    let mut set = None;
+    let mut config = None;
+    let mut profile = std::env::var(ENV_PROFILE).ok();
+    let mut sets = None;
+    let mut size = None;
+    let mut output = String::from("contact-sheet.png");
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config = Some(value(&mut args, arg)?),
+            "--profile" => profile = Some(String::from(value(&mut args, arg)?)),
+            "--sets" => sets = Some(value(&mut args, arg)?),
+            "--size" => {
+                size = Some(
+                    value(&mut args, arg)?
+                        .parse::<u32>()
+                        .map_err(|e| format!("invalid size: {e}"))?,
+                );
+            }
+            "--output" => output = String::from(value(&mut args, arg)?),
+            _ if set.is_none() && !arg.starts_with("--") => set = Some(arg.as_str()),
+            _ => return Err(format!("unexpected argument {arg}\n{USAGE}")),
+        }
+    }
+    let set = set.ok_or_else(|| String::from(USAGE))?;
+    let config = match config {
+        Some(config) => RoboHashConfig::load(config).and_then(RoboHashConfig::with_env),
+        None => RoboHashConfig::from_env(),
+    }
+    .map_err(|e| format!("{e}"))?;
+    let mut options = match &profile {
+        Some(profile) => config.profile(profile),
+        None => config.options(),
+    }
+    .map_err(|e| format!("{e}"))?;
+    if let Some(sets) = sets {
+        options = options.with_set_location(sets);
+    }
+    if let Some(size) = size {
+        options = options.with_size(size, size);
+    }
+    let sheet = contact_sheet(set, &options).map_err(|e| format!("{e}"))?;
+    std::fs::write(&output, sheet).map_err(|e| format!("failed to write {output}: {e}"))?;
+    println!("{output}");
+    Ok(())
+}
+
+fn value<'a>(args: &mut std::slice::Iter<'a, String>, flag: &str) -> Result<&'a str, String> {
+    match args.next() {
+        Some(value) => Ok(value),
+        None => Err(format!("{flag} needs a value\n{USAGE}")),
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::asset::{AssetSource, SymlinkPolicy};
+use crate::error::Error;
+use crate::hash::{self, Hasher};
+use crate::{materials, SetDefinition, SET_DEFAULT};
+
+const FINGERPRINT_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Catalogue {
+    sets: BTreeMap<String, String>,
+    backgrounds: BTreeMap<String, String>,
+}
+
+impl Catalogue {
+    pub fn scan(
+        source: &dyn AssetSource,
+        sets_root: &str,
+        background_root: &str,
+    ) -> Result<Self, Error> {
+        Self::scan_with_symlink_policy(source, sets_root, background_root, SymlinkPolicy::default())
+    }
+
+    pub fn scan_with_symlink_policy(
+        source: &dyn AssetSource,
+        sets_root: &str,
+        background_root: &str,
+        symlinks: SymlinkPolicy,
+    ) -> Result<Self, Error> {
+        let fingerprint =
+            |root: &str, set: &str| fingerprint(source, symlinks, root, &Path::new(root).join(set));
+        let mut sets = BTreeMap::new();
+        for set in directories(source, Path::new(sets_root))? {
+            let colours = match SetDefinition::load(source, symlinks, sets_root, &set)? {
+                Some(definition) => definition.colours,
+                None if set == SET_DEFAULT => {
+                    directories(source, &Path::new(sets_root).join(&set))?
+                }
+                None => Vec::new(),
+            };
+            for colour in colours {
+                let coloured_set = format!("{set}/{colour}");
+                materials::check_name(&coloured_set)?;
+                let fingerprint = fingerprint(sets_root, &coloured_set)?;
+                sets.insert(coloured_set, fingerprint);
+            }
+            let fingerprint = fingerprint(sets_root, &set)?;
+            sets.insert(set, fingerprint);
+        }
+
+        let mut backgrounds = BTreeMap::new();
+        let background_sets = match directories(source, Path::new(background_root)) {
+            Err(e) if e.is_not_found() => Vec::new(),
+            background_sets => background_sets?,
+        };
+        for set in background_sets {
+            let fingerprint = fingerprint(background_root, &set)?;
+            backgrounds.insert(set, fingerprint);
+        }
+        Ok(Self { sets, backgrounds })
+    }
+
+    pub fn sets(&self) -> impl Iterator<Item = &str> {
+        self.sets.keys().map(String::as_str)
+    }
+
+    pub fn background_sets(&self) -> impl Iterator<Item = &str> {
+        self.backgrounds.keys().map(String::as_str)
+    }
+
+    pub fn set_fingerprint(&self, set: &str) -> Option<&str> {
+        self.sets.get(set).map(String::as_str)
+    }
+
+    pub fn background_fingerprint(&self, background_set: &str) -> Option<&str> {
+        self.backgrounds.get(background_set).map(String::as_str)
+    }
+
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Hasher::new();
+        for (kind, fingerprints) in [("s", &self.sets), ("b", &self.backgrounds)] {
+            for (name, fingerprint) in fingerprints {
+                hasher.update(format!("{kind} {name} {fingerprint}\n").as_bytes());
+            }
+        }
+        let fingerprint = hasher.finish_hex();
+        String::from(&fingerprint[..FINGERPRINT_LENGTH])
+    }
+}
+
+fn directories(source: &dyn AssetSource, directory: &Path) -> Result<Vec<String>, Error> {
+    Ok(source
+        .list(directory)?
+        .into_iter()
+        .filter(|entry| source.is_dir(&directory.join(entry)))
+        .collect())
+}
+
+pub(crate) fn fingerprint(
+    source: &dyn AssetSource,
+    symlinks: SymlinkPolicy,
+    root: &str,
+    directory: &Path,
+) -> Result<String, Error> {
+    let mut hasher = Hasher::new();
+    let confine = |path: &Path| materials::confine(source, symlinks, root, &path.to_string_lossy());
+    fingerprint_directory(source, &confine, directory, "", &mut hasher)?;
+    let fingerprint = hasher.finish_hex();
+    Ok(String::from(&fingerprint[..FINGERPRINT_LENGTH]))
+}
+
+fn fingerprint_directory(
+    source: &dyn AssetSource,
+    confine: &dyn Fn(&Path) -> Result<(), Error>,
+    directory: &Path,
+    prefix: &str,
+    hasher: &mut Hasher,
+) -> Result<(), Error> {
+    for entry in source.list(directory)? {
+        let path = directory.join(&entry);
+        let relative = format!("{prefix}{entry}");
+        confine(&path)?;
+        if source.list(&path).is_ok() {
+            hasher.update(format!("d {relative}\n").as_bytes());
+            fingerprint_directory(source, confine, &path, &format!("{relative}/"), hasher)?;
+        } else {
+            let content = hash::sha512_bytes(&source.read(&path)?);
+            hasher.update(format!("f {relative} {content}\n").as_bytes());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::asset::MemoryAssetSource;
+    use crate::RoboHashBuilder;
+
+    fn assets() -> MemoryAssetSource {
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("sets/set4/000#body/000#body.png"), vec![1]);
+        assets.insert_file(Path::new("sets/set4/001#eyes/000#eyes.png"), vec![2]);
+        assets.insert_file(Path::new("backgrounds/bg1/000#bg.png"), vec![3]);
+        assets
+    }
+
+    #[test]
+    fn scan_fingerprints_every_set_and_background_set() {
+        // arrange
+        let assets = assets();
+        // act
+        let catalogue = Catalogue::scan(&assets, "sets", "backgrounds").unwrap();
+        // assert
+        assert_eq!(catalogue.sets().collect::<Vec<&str>>(), vec!["set4"]);
+        assert_eq!(
+            catalogue.background_sets().collect::<Vec<&str>>(),
+            vec!["bg1"]
+        );
+        assert_eq!(catalogue.set_fingerprint("set4").unwrap().len(), 32)
+    }
+
+    #[test]
+    fn scan_fingerprints_the_colours_declared_by_a_set_definition() {
+        // arrange
+        let mut assets = assets();
+        assets.insert_file(
+            Path::new("sets/custom/set.toml"),
+            br#"
+                colours = ["red"]
+                categories = [{ directory = "000#a" }]
+            "#
+            .to_vec(),
+        );
+        assets.insert_file(Path::new("sets/custom/red/000#a/000#a.png"), vec![7]);
+        // act
+        let catalogue = Catalogue::scan(&assets, "sets", "backgrounds").unwrap();
+        let resolution = RoboHashBuilder::new("test")
+            .with_set("custom")
+            .with_asset_source(assets)
+            .with_catalogue(Arc::new(catalogue.clone()))
+            .build()
+            .and_then(|robo_hash| robo_hash.resolve());
+        // assert
+        assert_eq!(
+            catalogue.sets().collect::<Vec<&str>>(),
+            vec!["custom", "custom/red", "set4"]
+        );
+        assert_eq!(resolution.unwrap().set, "custom/red");
+        assert_ne!(
+            catalogue.set_fingerprint("custom/red"),
+            catalogue.set_fingerprint("custom")
+        )
+    }
+
+    #[test]
+    fn scan_skips_files_next_to_the_set_directories() {
+        // arrange
+        let mut assets = assets();
+        assets.insert_file(Path::new("sets/.DS_Store"), vec![4]);
+        assets.insert_file(Path::new("sets/README.md"), vec![5]);
+        assets.insert_file(Path::new("backgrounds/.DS_Store"), vec![6]);
+        // act
+        let catalogue = Catalogue::scan(&assets, "sets", "backgrounds").unwrap();
+        // assert
+        assert_eq!(catalogue.sets().collect::<Vec<&str>>(), vec!["set4"]);
+        assert_eq!(
+            catalogue.background_sets().collect::<Vec<&str>>(),
+            vec!["bg1"]
+        )
+    }
+
+    #[test]
+    fn scan_returns_errors_other_than_a_missing_background_root() {
+        // arrange
+        #[derive(Debug)]
+        struct Unreadable(MemoryAssetSource);
+        impl AssetSource for Unreadable {
+            fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+                match directory.ends_with("backgrounds") {
+                    true => Err(Error::IoError(std::io::ErrorKind::PermissionDenied.into())),
+                    false => self.0.list(directory),
+                }
+            }
+            fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+                self.0.read(file)
+            }
+        }
+        // act
+        let missing = Catalogue::scan(&assets(), "sets", "missing");
+        let unreadable = Catalogue::scan(&Unreadable(assets()), "sets", "backgrounds");
+        // assert
+        assert_eq!(missing.unwrap().background_sets().count(), 0);
+        assert!(matches!(unreadable, Err(Error::IoError(_))))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_applies_the_symlink_policy_while_fingerprinting() {
+        // arrange
+        let directory =
+            std::env::temp_dir().join(format!("robohash-catalogue-{}", std::process::id()));
+        let sets_root = directory.join("sets");
+        std::fs::create_dir_all(sets_root.join("linked/000#a")).unwrap();
+        std::fs::write(directory.join("secret.png"), [0]).unwrap();
+        std::os::unix::fs::symlink(
+            directory.join("secret.png"),
+            sets_root.join("linked/000#a/000#a.png"),
+        )
+        .unwrap();
+        let scan = |symlinks| {
+            Catalogue::scan_with_symlink_policy(
+                &crate::FileSystemAssetSource,
+                sets_root.to_str().unwrap(),
+                directory.join("backgrounds").to_str().unwrap(),
+                symlinks,
+            )
+        };
+        // act
+        let deny = scan(SymlinkPolicy::Deny);
+        let within_root = scan(SymlinkPolicy::WithinRoot);
+        let follow = scan(SymlinkPolicy::Follow);
+        std::fs::remove_dir_all(&directory).unwrap();
+        // assert
+        assert!(matches!(deny, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(within_root, Err(Error::AssetOutsideRoot(_))));
+        assert_eq!(
+            follow.unwrap().sets().collect::<Vec<&str>>(),
+            vec!["linked"]
+        )
+    }
+
+    #[test]
+    fn fingerprint_changes_when_a_file_is_replaced() {
+        // arrange
+        let assets = assets();
+        let mut replaced = assets.clone();
+        replaced.insert_file(Path::new("sets/set4/001#eyes/000#eyes.png"), vec![9]);
+        // act
+        let original =
+            fingerprint(&assets, SymlinkPolicy::Deny, "sets", Path::new("sets/set4")).unwrap();
+        let changed = fingerprint(
+            &replaced,
+            SymlinkPolicy::Deny,
+            "sets",
+            Path::new("sets/set4"),
+        )
+        .unwrap();
+        // assert
+        assert_ne!(original, changed)
+    }
+
+    #[test]
+    fn fingerprint_changes_when_the_layout_changes() {
+        // arrange
+        let assets = assets();
+        let mut renamed = MemoryAssetSource::new();
+        renamed.insert_file(Path::new("sets/set4/000#body/000#body.png"), vec![1]);
+        renamed.insert_file(Path::new("sets/set4/002#eyes/000#eyes.png"), vec![2]);
+        // act
+        let original =
+            fingerprint(&assets, SymlinkPolicy::Deny, "sets", Path::new("sets/set4")).unwrap();
+        let changed = fingerprint(
+            &renamed,
+            SymlinkPolicy::Deny,
+            "sets",
+            Path::new("sets/set4"),
+        )
+        .unwrap();
+        // assert
+        assert_ne!(original, changed)
+    }
+}
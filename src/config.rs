@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::{Algorithm, Format, RenderOptions, RoboHashBuilder, SizeDefinition};
+
+const ENV_PREFIX: &str = "ROBOHASH_";
+const ENV_CONFIG: &str = "ROBOHASH_CONFIG";
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoboHashConfig {
+    pub defaults: Profile,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub set_root: Option<String>,
+    pub background_root: Option<String>,
+    pub set: Option<String>,
+    pub colour: Option<String>,
+    pub background_set: Option<String>,
+    pub size: Option<SizeDefinition>,
+    #[serde(with = "format_name")]
+    pub format: Option<Format>,
+    pub quality: Option<u8>,
+    #[serde(with = "algorithm_name")]
+    pub algorithm: Option<Algorithm>,
+}
+
+impl RoboHashConfig {
+    pub fn from_toml(config: &str) -> Result<Self, Error> {
+        let config: Self = match toml::from_str(config) {
+            Ok(config) => config,
+            Err(e) => return Err(Error::InvalidConfig(format!("{e}"))),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_env() -> Result<Self, Error> {
+        let config = match std::env::var_os(ENV_CONFIG) {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+        config.with_env()
+    }
+
+    pub fn with_env(self) -> Result<Self, Error> {
+        self.with_vars(robohash_vars(std::env::vars_os())?)
+    }
+
+    pub fn with_vars<I, K, V>(mut self, vars: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (key, value) in vars {
+            let Some(key) = key.as_ref().strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let value = String::from(value.as_ref());
+            let defaults = &mut self.defaults;
+            match key {
+                "SET_ROOT" => defaults.set_root = Some(value),
+                "BACKGROUND_ROOT" => defaults.background_root = Some(value),
+                "SET" => defaults.set = Some(value),
+                "COLOUR" => defaults.colour = Some(value),
+                "BACKGROUND_SET" => defaults.background_set = Some(value),
+                "SIZE" => defaults.size = Some(parse_size(&value)?),
+                "FORMAT" => defaults.format = Some(parse_format(&value)?),
+                "QUALITY" => match value.parse::<u8>() {
+                    Ok(quality) => defaults.quality = Some(quality),
+                    Err(e) => {
+                        return Err(Error::InvalidConfig(format!(
+                            "{ENV_PREFIX}QUALITY {value}: {e}"
+                        )))
+                    }
+                },
+                "ALGORITHM" => defaults.algorithm = Some(parse_algorithm(&value)?),
+                "CONFIG" | "PROFILE" => {}
+                _ => {
+                    return Err(Error::InvalidConfig(format!(
+                        "unknown variable {ENV_PREFIX}{key}"
+                    )))
+                }
+            }
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    pub fn options(&self) -> Result<RenderOptions, Error> {
+        self.defaults.apply(RenderOptions::new())
+    }
+
+    pub fn profile(&self, name: &str) -> Result<RenderOptions, Error> {
+        match self.profiles.get(name) {
+            Some(profile) => profile.apply(self.options()?),
+            None => Err(Error::UnknownProfile(String::from(name))),
+        }
+    }
+
+    pub fn builder(&self, text: &str) -> Result<RoboHashBuilder, Error> {
+        Ok(RoboHashBuilder::new(text).with_options(self.options()?))
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        self.options()?;
+        for name in self.profiles.keys() {
+            self.profile(name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Profile {
+    pub fn apply(&self, mut options: RenderOptions) -> Result<RenderOptions, Error> {
+        if let Some(set_root) = &self.set_root {
+            options = options.with_set_location(set_root);
+        }
+        if let Some(background_root) = &self.background_root {
+            options = options.with_background_location(background_root);
+        }
+        if let Some(set) = &self.set {
+            options = options.with_set(set);
+        }
+        if let Some(colour) = &self.colour {
+            options = options.with_colour(colour);
+        }
+        if let Some(background_set) = &self.background_set {
+            options = options.with_background_set(background_set);
+        }
+        if let Some(size) = &self.size {
+            if size.width == 0 || size.height == 0 {
+                return Err(Error::InvalidConfig(format!(
+                    "size {}x{}",
+                    size.width, size.height
+                )));
+            }
+            options = options.with_size(size.width, size.height);
+        }
+        if let Some(format) = self.format(options.format)? {
+            options = options.with_format(format);
+        }
+        if let Some(algorithm) = self.algorithm {
+            options = options.with_algorithm(algorithm);
+        }
+        Ok(options)
+    }
+
+    fn format(&self, current: Format) -> Result<Option<Format>, Error> {
+        let format = match (self.format, self.quality, current) {
+            (format, None, _) => format,
+            (Some(Format::Jpeg { .. }), Some(quality), _)
+            | (None, Some(quality), Format::Jpeg { .. }) => Some(Format::Jpeg { quality }),
+            (_, Some(_), _) => {
+                return Err(Error::InvalidConfig(String::from(
+                    "quality needs format = \"jpeg\"",
+                )))
+            }
+        };
+        if let Some(Format::Jpeg { quality }) = format {
+            if !(1..=100).contains(&quality) {
+                return Err(Error::InvalidConfig(format!(
+                    "quality {quality} is not between 1 and 100"
+                )));
+            }
+        }
+        Ok(format)
+    }
+}
+
+fn parse_format(format: &str) -> Result<Format, Error> {
+    match format.to_lowercase().as_str() {
+        "png" => Ok(Format::Png),
+        "jpeg" | "jpg" => Ok(Format::Jpeg {
+            quality: DEFAULT_JPEG_QUALITY,
+        }),
+        _ => Err(Error::InvalidConfig(format!("unknown format {format}"))),
+    }
+}
+
+fn parse_algorithm(algorithm: &str) -> Result<Algorithm, Error> {
+    match algorithm.to_lowercase().as_str() {
+        "v1" => Ok(Algorithm::V1),
+        "v2" => Ok(Algorithm::V2),
+        _ => Err(Error::InvalidConfig(format!(
+            "unknown algorithm {algorithm}"
+        ))),
+    }
+}
+
+mod format_name {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::Format;
+
+    pub(super) fn serialize<S: Serializer>(
+        format: &Option<Format>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match format {
+            Some(Format::Png) => serializer.serialize_some("png"),
+            Some(Format::Jpeg { .. }) => serializer.serialize_some("jpeg"),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Format>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(format) => super::parse_format(&format)
+                .map(Some)
+                .map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+mod algorithm_name {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::Algorithm;
+
+    pub(super) fn serialize<S: Serializer>(
+        algorithm: &Option<Algorithm>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match algorithm {
+            Some(Algorithm::V1) => serializer.serialize_some("v1"),
+            Some(Algorithm::V2) => serializer.serialize_some("v2"),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Algorithm>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(algorithm) => super::parse_algorithm(&algorithm)
+                .map(Some)
+                .map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+fn robohash_vars<I>(vars: I) -> Result<Vec<(String, String)>, Error>
+where
+    I: IntoIterator<Item = (OsString, OsString)>,
+{
+    let mut robohash_vars = Vec::new();
+    for (key, value) in vars {
+        let name = key.to_string_lossy().into_owned();
+        if !name.starts_with(ENV_PREFIX) {
+            continue;
+        }
+        match (key.into_string(), value.into_string()) {
+            (Ok(key), Ok(value)) => robohash_vars.push((key, value)),
+            _ => return Err(Error::InvalidConfig(format!("{name} is not valid UTF-8"))),
+        }
+    }
+    Ok(robohash_vars)
+}
+
+fn parse_size(size: &str) -> Result<SizeDefinition, Error> {
+    let (width, height) = size.split_once('x').unwrap_or((size, size));
+    match (width.trim().parse::<u32>(), height.trim().parse::<u32>()) {
+        (Ok(width), Ok(height)) => Ok(SizeDefinition { width, height }),
+        _ => Err(Error::InvalidConfig(format!("{ENV_PREFIX}SIZE {size}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Background;
+
+    const CONFIG: &str = r#"
+[defaults]
+set_root = "/srv/robohash/sets"
+set = "set4"
+size = { width = 256, height = 256 }
+
+[profiles.thumbnail]
+size = { width = 64, height = 64 }
+format = "jpeg"
+quality = 80
+
+[profiles.legacy]
+set = "set1"
+colour = "blue"
+background_set = "bg1"
+"#;
+
+    #[test]
+    fn from_toml_applies_defaults_to_the_render_options() {
+        // arrange
+        let config = RoboHashConfig::from_toml(CONFIG).unwrap();
+        // act
+        let options = config.options().unwrap();
+        // assert
+        let expected = RenderOptions::new()
+            .with_set_location("/srv/robohash/sets")
+            .with_set("set4")
+            .with_size(256, 256);
+        assert_eq!(options, expected)
+    }
+
+    #[test]
+    fn profile_overrides_the_defaults() {
+        // arrange
+        let config = RoboHashConfig::from_toml(CONFIG).unwrap();
+        // act
+        let thumbnail = config.profile("thumbnail").unwrap();
+        let legacy = config.profile("legacy").unwrap();
+        let missing = config.profile("missing");
+        let inherited = RoboHashConfig::from_toml(
+            "[defaults]\nformat = \"jpeg\"\n[profiles.a]\nquality = 70\n",
+        )
+        .and_then(|config| config.profile("a"));
+        // assert
+        assert_eq!(thumbnail.set, "set4");
+        assert_eq!(thumbnail.format, Format::Jpeg { quality: 80 });
+        assert_eq!(
+            thumbnail,
+            config
+                .options()
+                .unwrap()
+                .with_size(64, 64)
+                .with_format(Format::Jpeg { quality: 80 })
+        );
+        assert_eq!(legacy.colour.as_deref(), Some("blue"));
+        assert_eq!(legacy.background, Background::Set(String::from("bg1")));
+        assert!(matches!(missing, Err(Error::UnknownProfile(_))));
+        assert_eq!(inherited.unwrap().format, Format::Jpeg { quality: 70 })
+    }
+
+    #[test]
+    fn from_toml_reads_format_and_algorithm_into_their_types() {
+        // act
+        let config = RoboHashConfig::from_toml(
+            "[defaults]\nformat = \"JPG\"\nalgorithm = \"v2\"\n[profiles.a]\nformat = \"png\"\n",
+        )
+        .unwrap();
+        // assert
+        assert_eq!(
+            config.defaults.format,
+            Some(Format::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY
+            })
+        );
+        assert_eq!(config.defaults.algorithm, Some(Algorithm::V2));
+        assert_eq!(config.profiles["a"].format, Some(Format::Png));
+        assert_eq!(
+            toml::from_str::<RoboHashConfig>(&toml::to_string(&config).unwrap()).unwrap(),
+            config
+        )
+    }
+
+    #[test]
+    fn with_vars_overrides_the_defaults_from_robohash_variables() {
+        // arrange
+        let config = RoboHashConfig::from_toml(CONFIG).unwrap();
+        let vars = [
+            ("ROBOHASH_SET", "set2"),
+            ("ROBOHASH_SIZE", "300x200"),
+            ("ROBOHASH_FORMAT", "png"),
+            ("ROBOHASH_ALGORITHM", "v2"),
+            ("PATH", "/usr/bin"),
+        ];
+        // act
+        let config = config.with_vars(vars).unwrap();
+        // assert
+        let options = config.options().unwrap();
+        assert_eq!(options.set, "set2");
+        assert_eq!(options.set_root, "/srv/robohash/sets");
+        assert_eq!(options, options.clone().with_size(300, 200));
+        assert_eq!(options.algorithm, Algorithm::V2);
+        assert_eq!(config.profile("thumbnail").unwrap().set, "set2")
+    }
+
+    #[test]
+    fn invalid_values_are_rejected_when_loading() {
+        // act
+        let unknown_field = RoboHashConfig::from_toml("[defaults]\nsize = 12\n");
+        let unknown_format = RoboHashConfig::from_toml("[profiles.a]\nformat = \"gif\"\n");
+        let unknown_algorithm = toml::from_str::<Profile>("algorithm = \"v3\"\n");
+        let invalid_format = RoboHashConfig::default().with_vars([("ROBOHASH_FORMAT", "gif")]);
+        let png_quality = RoboHashConfig::from_toml("[defaults]\nquality = 80\n");
+        let zero_quality =
+            RoboHashConfig::from_toml("[defaults]\nformat = \"jpeg\"\nquality = 0\n");
+        let zero_size =
+            RoboHashConfig::from_toml("[profiles.a]\nsize = { width = 0, height = 64 }\n");
+        let invalid_size = RoboHashConfig::default().with_vars([("ROBOHASH_SIZE", "big")]);
+        let unknown_var = RoboHashConfig::default().with_vars([("ROBOHASH_SIZ", "64")]);
+        let cli_vars = RoboHashConfig::default().with_vars([
+            ("ROBOHASH_CONFIG", "robohash.toml"),
+            ("ROBOHASH_PROFILE", "a"),
+        ]);
+        // assert
+        assert!(matches!(unknown_field, Err(Error::InvalidConfig(_))));
+        assert!(matches!(unknown_format, Err(Error::InvalidConfig(_))));
+        assert!(unknown_algorithm.is_err());
+        assert!(matches!(invalid_format, Err(Error::InvalidConfig(_))));
+        assert!(matches!(png_quality, Err(Error::InvalidConfig(_))));
+        assert!(matches!(zero_quality, Err(Error::InvalidConfig(_))));
+        assert!(matches!(zero_size, Err(Error::InvalidConfig(_))));
+        assert!(matches!(invalid_size, Err(Error::InvalidConfig(_))));
+        assert!(matches!(unknown_var, Err(Error::InvalidConfig(_))));
+        assert_eq!(cli_vars.unwrap(), RoboHashConfig::default())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn robohash_vars_skips_other_variables_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        // arrange
+        let invalid = || OsString::from_vec(vec![0xff, 0xfe]);
+        let var = |key: &str, value: OsString| (OsString::from(key), value);
+        // act
+        let vars = robohash_vars([
+            var("ROBOHASH_SET", OsString::from("set2")),
+            var("LANG", invalid()),
+            (invalid(), OsString::from("value")),
+        ]);
+        let invalid_value = robohash_vars([var("ROBOHASH_SET", invalid())]);
+        // assert
+        assert_eq!(
+            vars.unwrap(),
+            [(String::from("ROBOHASH_SET"), String::from("set2"))]
+        );
+        assert!(matches!(invalid_value, Err(Error::InvalidConfig(_))))
+    }
+}
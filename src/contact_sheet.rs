@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use image::{imageops, Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use rusttype::{Font, Scale};
+
+use crate::error::Error;
+use crate::image::{self as robo_image, PartCache};
+use crate::{materials, ImageSize, RenderOptions, RoboHashBuilder};
+
+const DEFAULT_CELL_SIZE: u32 = 128;
+const HEADER_WIDTH: u32 = 128;
+const LABEL_HEIGHT: u32 = 16;
+const LABEL_SCALE: f32 = 12.0;
+const LABEL_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+
+pub fn contact_sheet(set: &str, options: &RenderOptions) -> Result<Vec<u8>, Error> {
+    let cell = options.image_size.unwrap_or(ImageSize {
+        width: DEFAULT_CELL_SIZE,
+        height: DEFAULT_CELL_SIZE,
+    });
+    let options = options
+        .clone()
+        .with_set(set)
+        .with_size(cell.width, cell.height);
+    let base = RoboHashBuilder::new("")
+        .with_options(options.clone())
+        .build()?;
+    let source = base.asset_source.as_ref();
+    let categories = base.categories(source)?;
+    let rows = categories
+        .iter()
+        .map(|category| materials::files_in_category(source, &base.sets_root, &base.set, category))
+        .collect::<Result<Vec<Vec<String>>, Error>>()?;
+    let base_parts = categories
+        .iter()
+        .map(|category| (category.as_str(), 0))
+        .collect::<Vec<(&str, usize)>>();
+
+    let font = match Font::try_from_bytes(LABEL_FONT) {
+        Some(font) => font,
+        None => return Err(Error::FontLoadFailed(String::from("DejaVuSansMono.ttf"))),
+    };
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0) as u32;
+    let row_height = cell.height.saturating_add(LABEL_HEIGHT);
+    let (width, height) = match (
+        columns
+            .checked_mul(cell.width)
+            .and_then(|width| width.checked_add(HEADER_WIDTH)),
+        (rows.len() as u32).checked_mul(row_height),
+    ) {
+        (Some(width), Some(height)) => (width, height.max(1)),
+        _ => return Err(Error::InvalidImageSize(cell.width, cell.height)),
+    };
+    ImageSize { width, height }.check()?;
+    let mut sheet = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+    let cache = PartCache::default();
+    for (row, (category, files)) in categories.iter().zip(&rows).enumerate() {
+        let y = row as u32 * row_height;
+        draw_label(
+            &mut sheet,
+            &font,
+            0,
+            y + cell.height / 2,
+            HEADER_WIDTH,
+            category,
+        );
+        for (column, file) in files.iter().enumerate() {
+            let mut parts = base_parts.clone();
+            parts.push((category, column));
+            let robo_hash = RoboHashBuilder::new("")
+                .with_options(options.clone().with_parts(&parts))
+                .build()?;
+            let image = robo_hash.assemble_image(cell, Some(&cache))?;
+            let x = HEADER_WIDTH + column as u32 * cell.width;
+            imageops::overlay(&mut sheet, &image, x as i64, y as i64);
+            let name = match Path::new(file).file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => file.to_owned(),
+            };
+            draw_label(&mut sheet, &font, x, y + cell.height, cell.width, &name);
+        }
+    }
+    robo_image::encode(&sheet, options.format)
+}
+
+fn draw_label(sheet: &mut RgbaImage, font: &Font, x: u32, y: u32, width: u32, text: &str) {
+    let scale = Scale::uniform(LABEL_SCALE);
+    let mut label = String::from(text);
+    while !label.is_empty() && text_size(scale, font, &label).0 > width as i32 - 4 {
+        label.pop();
+    }
+    draw_text_mut(
+        sheet,
+        Rgba([0, 0, 0, 255]),
+        x as i32 + 2,
+        y as i32 + 2,
+        scale,
+        font,
+        &label,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::MemoryAssetSource;
+    use crate::format::Format;
+
+    fn part(colour: [u8; 4]) -> Vec<u8> {
+        robo_image::encode(&RgbaImage::from_pixel(4, 4, Rgba(colour)), Format::Png).unwrap()
+    }
+
+    fn assets() -> MemoryAssetSource {
+        let mut assets = MemoryAssetSource::new();
+        let sets = Path::new("./sets/custom");
+        assets.insert_file(&sets.join("000#a/000#a0.png"), part([255, 0, 0, 255]));
+        assets.insert_file(&sets.join("000#a/001#a1.png"), part([0, 255, 0, 255]));
+        assets.insert_file(&sets.join("001#b/000#b0.png"), part([0, 0, 0, 0]));
+        assets.insert_file(&sets.join("001#b/001#b1.png"), part([0, 0, 0, 0]));
+        assets.insert_file(&sets.join("001#b/002#b2.png"), part([0, 0, 255, 255]));
+        assets
+    }
+
+    #[test]
+    fn contact_sheet_has_a_row_per_category_and_a_cell_per_part() {
+        // arrange
+        let options = RenderOptions::new()
+            .with_asset_source(assets())
+            .with_size(32, 32);
+        // act
+        let sheet = contact_sheet("custom", &options).unwrap();
+        // assert
+        let sheet = image::load_from_memory(&sheet).unwrap().to_rgba8();
+        assert_eq!(sheet.dimensions(), (HEADER_WIDTH + 3 * 32, 2 * 48))
+    }
+
+    #[test]
+    fn contact_sheet_composites_each_part_over_the_base_robot() {
+        // arrange
+        let options = RenderOptions::new()
+            .with_asset_source(assets())
+            .with_size(32, 32);
+        // act
+        let sheet = contact_sheet("custom", &options).unwrap();
+        // assert
+        let sheet = image::load_from_memory(&sheet).unwrap().to_rgba8();
+        let cell = |column: u32, row: u32| {
+            *sheet.get_pixel(HEADER_WIDTH + column * 32 + 16, row * 48 + 16)
+        };
+        assert_eq!(cell(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(cell(1, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(cell(0, 1), Rgba([255, 0, 0, 255]));
+        assert_eq!(cell(2, 1), Rgba([0, 0, 255, 255]))
+    }
+}
@@ -6,14 +6,48 @@ pub enum Error {
     ParseIntError(#[from] std::num::ParseIntError),
     #[error("io error")]
     IoError(#[from] std::io::Error),
+    #[cfg(feature = "render")]
     #[error("error processing image")]
     ImageProcessingError(#[from] image::ImageError),
     #[error("failed loading image {0}")]
     ImageOpenFailed(String),
+    #[cfg(feature = "contact-sheet")]
+    #[error("failed loading font {0}")]
+    FontLoadFailed(String),
     #[error("failed to fetch index {0}[{1}]")]
     InvalidArrayIndex(String, String),
+    #[error("invalid image size {0}x{1}")]
+    InvalidImageSize(u32, u32),
+    #[error("invalid set definition: {0}")]
+    InvalidSetDefinition(String),
+    #[error("malformed assets: {0}")]
+    MalformedAssets(String),
+    #[error("a catalogue is required to {0}")]
+    CatalogueRequired(String),
+    #[error("invalid cache key {0}")]
+    InvalidCacheKey(String),
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("unknown asset {0}")]
+    UnknownAsset(String),
+    #[error("asset {0} is outside of its root")]
+    AssetOutsideRoot(String),
+    #[error("unknown profile {0}")]
+    UnknownProfile(String),
+    #[error("unknown tenant {0}")]
+    UnknownTenant(String),
+    #[error("{0} not allowed")]
+    NotAllowed(String),
+    #[error("render task failed: {0}")]
+    RenderTaskFailed(String),
     #[error("Is missing required data. Please use the RoboHashBuilder.")]
     RoboHashMissingRequiredData,
     #[error("unknown error")]
     Unknown,
 }
+
+impl Error {
+    pub(crate) fn is_not_found(&self) -> bool {
+        matches!(self, Error::IoError(e) if e.kind() == std::io::ErrorKind::NotFound)
+    }
+}
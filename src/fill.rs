@@ -0,0 +1,189 @@
+use image::RgbaImage;
+
+use crate::background::{Backdrop, Rgba};
+use crate::palette;
+
+const HASH_DERIVED_MIN_CONTRAST: f32 = 3.0;
+
+pub(crate) fn fill(image: &mut RgbaImage, backdrop: &Backdrop) {
+    match backdrop {
+        Backdrop::Solid(colour) => fill_solid(image, *colour),
+        Backdrop::LinearGradient { from, to, angle } => {
+            fill_linear_gradient(image, *from, *to, *angle)
+        }
+        Backdrop::RadialGradient { inner, outer } => fill_radial_gradient(image, *inner, *outer),
+        Backdrop::None | Backdrop::Image(_) | Backdrop::HashDerived { .. } => {}
+    }
+}
+
+pub(crate) fn hash_derived_colours(
+    hue_seed: i64,
+    offset_seed: i64,
+    robot: &RgbaImage,
+) -> (Rgba, Rgba) {
+    let robot_luminance = relative_luminance(average_colour(robot));
+    let lighten = contrast_ratio(1.0, robot_luminance) >= contrast_ratio(0.0, robot_luminance);
+    let hue = hue_seed.rem_euclid(360) as f32;
+    let second_hue = (hue + 20.0 + offset_seed.rem_euclid(40) as f32) % 360.0;
+    let saturation = 0.45;
+    let mut lightness: f32 = if lighten { 0.8 } else { 0.25 };
+    loop {
+        let from = palette::hsl_to_rgba(hue, saturation, lightness);
+        let to = palette::hsl_to_rgba(second_hue, saturation, lightness);
+        let contrast = contrast_ratio(relative_luminance(from.0), robot_luminance)
+            .min(contrast_ratio(relative_luminance(to.0), robot_luminance));
+        let at_limit = lightness <= 0.0 || lightness >= 1.0;
+        if contrast >= HASH_DERIVED_MIN_CONTRAST || at_limit {
+            return (Rgba(from.0), Rgba(to.0));
+        }
+        lightness = if lighten {
+            (lightness + 0.05).min(1.0)
+        } else {
+            (lightness - 0.05).max(0.0)
+        };
+    }
+}
+
+fn fill_solid(image: &mut RgbaImage, colour: Rgba) {
+    for pixel in image.pixels_mut() {
+        pixel.0 = colour.0;
+    }
+}
+
+fn fill_linear_gradient(image: &mut RgbaImage, from: Rgba, to: Rgba, angle: f32) {
+    let (width, height) = image.dimensions();
+    let half_width = width as f32 / 2.0;
+    let half_height = height as f32 / 2.0;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let extent = (half_width * cos).abs() + (half_height * sin).abs();
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let px = x as f32 + 0.5 - half_width;
+        let py = y as f32 + 0.5 - half_height;
+        let position = (px * cos + py * sin) / extent.max(f32::EPSILON);
+        pixel.0 = lerp(from, to, (position + 1.0) / 2.0).0;
+    }
+}
+
+fn fill_radial_gradient(image: &mut RgbaImage, inner: Rgba, outer: Rgba) {
+    let (width, height) = image.dimensions();
+    let half_width = width as f32 / 2.0;
+    let half_height = height as f32 / 2.0;
+    let radius = (half_width * half_width + half_height * half_height).sqrt();
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let px = x as f32 + 0.5 - half_width;
+        let py = y as f32 + 0.5 - half_height;
+        let distance = (px * px + py * py).sqrt();
+        pixel.0 = lerp(inner, outer, distance / radius.max(f32::EPSILON)).0;
+    }
+}
+
+fn lerp(from: Rgba, to: Rgba, t: f32) -> Rgba {
+    let t = t.clamp(0.0, 1.0);
+    let mut colour = [0; 4];
+    for (channel, value) in colour.iter_mut().enumerate() {
+        let mixed = from.0[channel] as f32 + (to.0[channel] as f32 - from.0[channel] as f32) * t;
+        *value = mixed.round() as u8;
+    }
+    Rgba(colour)
+}
+
+fn average_colour(image: &RgbaImage) -> [u8; 4] {
+    let mut totals = [0_f64; 3];
+    let mut weight = 0_f64;
+    for pixel in image.pixels() {
+        let alpha = pixel[3] as f64;
+        for (channel, total) in totals.iter_mut().enumerate() {
+            *total += pixel[channel] as f64 * alpha;
+        }
+        weight += alpha;
+    }
+    if weight == 0.0 {
+        return [128, 128, 128, 255];
+    }
+    let channel = |total: f64| (total / weight).round() as u8;
+    [
+        channel(totals[0]),
+        channel(totals[1]),
+        channel(totals[2]),
+        255,
+    ]
+}
+
+fn relative_luminance(colour: [u8; 4]) -> f32 {
+    let linear = |value: u8| {
+        let value = value as f32 / 255.0;
+        if value <= 0.039_28 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(colour[0]) + 0.7152 * linear(colour[1]) + 0.0722 * linear(colour[2])
+}
+
+fn contrast_ratio(first: f32, second: f32) -> f32 {
+    let (lighter, darker) = if first > second {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_solid_colours_every_pixel() {
+        // arrange
+        let mut image = RgbaImage::new(4, 4);
+        let colour = Rgba([1, 2, 3, 255]);
+        // act
+        fill(&mut image, &Backdrop::Solid(colour));
+        // assert
+        assert!(image.pixels().all(|pixel| pixel.0 == colour.0))
+    }
+
+    #[test]
+    fn fill_linear_gradient_runs_from_start_to_end_colour() {
+        // arrange
+        let mut image = RgbaImage::new(64, 4);
+        let from = Rgba([0, 0, 0, 255]);
+        let to = Rgba([255, 255, 255, 255]);
+        // act
+        fill(
+            &mut image,
+            &Backdrop::LinearGradient {
+                from,
+                to,
+                angle: 0.0,
+            },
+        );
+        // assert
+        assert!(image.get_pixel(0, 0)[0] < 8);
+        assert!(image.get_pixel(63, 0)[0] > 247)
+    }
+
+    #[test]
+    fn hash_derived_colours_have_minimum_contrast_against_the_robot() {
+        // arrange
+        let robots = [
+            RgbaImage::from_pixel(8, 8, image::Rgba([20, 20, 20, 255])),
+            RgbaImage::from_pixel(8, 8, image::Rgba([120, 120, 120, 255])),
+            RgbaImage::from_pixel(8, 8, image::Rgba([240, 240, 240, 255])),
+        ];
+        for robot in robots.iter() {
+            let robot_luminance = relative_luminance(average_colour(robot));
+            for seed in [0, 45, 123, 300] {
+                // act
+                let (from, to) = hash_derived_colours(seed, seed * 7, robot);
+                // assert
+                let from_contrast = contrast_ratio(relative_luminance(from.0), robot_luminance);
+                let to_contrast = contrast_ratio(relative_luminance(to.0), robot_luminance);
+                assert!(from_contrast >= HASH_DERIVED_MIN_CONTRAST);
+                assert!(to_contrast >= HASH_DERIVED_MIN_CONTRAST)
+            }
+        }
+    }
+}
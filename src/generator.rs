@@ -0,0 +1,181 @@
+#[cfg(feature = "parallel")]
+use rayon::iter::{ParallelBridge, ParallelIterator};
+
+use crate::error::Error;
+use crate::image::PartCache;
+use crate::{RenderOptions, RoboHashBuilder};
+
+#[derive(Debug, Default)]
+pub struct RoboHashGenerator {
+    cache: PartCache,
+}
+
+#[cfg(feature = "parallel")]
+#[derive(Debug)]
+pub struct BatchItem {
+    pub index: usize,
+    pub input: String,
+    pub result: Result<String, Error>,
+}
+
+impl RoboHashGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_part_cache_limit(mut self, max_bytes: u64) -> Self {
+        self.cache = PartCache::new(max_bytes);
+        self
+    }
+
+    pub fn render(&self, text: &str, options: &RenderOptions) -> Result<String, Error> {
+        let robo_hash = RoboHashBuilder::new(text)
+            .with_options(options.clone())
+            .build()?;
+        robo_hash.assemble_base64_cached(&self.cache)
+    }
+
+    pub fn render_bytes(&self, text: &str, options: &RenderOptions) -> Result<Vec<u8>, Error> {
+        let robo_hash = RoboHashBuilder::new(text)
+            .with_options(options.clone())
+            .build()?;
+        robo_hash.assemble_cached(&self.cache)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn render_batch<I, F>(&self, inputs: I, options: &RenderOptions, on_result: F)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str> + Send,
+        I::IntoIter: Send,
+        F: Fn(BatchItem) + Sync + Send,
+    {
+        inputs
+            .into_iter()
+            .enumerate()
+            .par_bridge()
+            .for_each(|(index, input)| {
+                let input = input.as_ref();
+                let result = self.render(input, options);
+                on_result(BatchItem {
+                    index,
+                    input: String::from(input),
+                    result,
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::asset::MemoryAssetSource;
+    use crate::Format;
+
+    fn assets(colour: [u8; 4]) -> MemoryAssetSource {
+        let part =
+            crate::image::encode(&RgbaImage::from_pixel(4, 4, Rgba(colour)), Format::Png).unwrap();
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("./sets/custom/000#a/000#a0.png"), part);
+        assets
+    }
+
+    #[test]
+    fn render_returns_the_same_image_as_the_builder() {
+        // arrange
+        let generator = RoboHashGenerator::new();
+        let options = RenderOptions::new().with_set("set4").with_size(64, 64);
+        let expected = RoboHashBuilder::new("test")
+            .with_options(options.clone())
+            .build()
+            .unwrap()
+            .assemble_base64()
+            .unwrap();
+        // act
+        let rendered = generator.render("test", &options);
+        // assert
+        assert_eq!(rendered.unwrap(), expected)
+    }
+
+    #[test]
+    fn render_bytes_returns_the_same_image_as_the_builder() {
+        // arrange
+        let generator = RoboHashGenerator::new();
+        let options = RenderOptions::new().with_set("set4").with_size(64, 64);
+        let expected = RoboHashBuilder::new("test")
+            .with_options(options.clone())
+            .build()
+            .unwrap()
+            .assemble()
+            .unwrap();
+        // act
+        let rendered = generator.render_bytes("test", &options);
+        // assert
+        assert_eq!(rendered.unwrap(), expected)
+    }
+
+    #[test]
+    fn render_keeps_parts_of_sources_with_the_same_paths_apart() {
+        // arrange
+        let generator = RoboHashGenerator::new();
+        let options = |colour| {
+            RenderOptions::new()
+                .with_asset_source(assets(colour))
+                .with_set("custom")
+                .with_size(4, 4)
+        };
+        let red = options([255, 0, 0, 255]);
+        let blue = options([0, 0, 255, 255]);
+        // act
+        let first = generator.render_bytes("test", &red).unwrap();
+        let second = generator.render_bytes("test", &blue).unwrap();
+        // assert
+        let pixel = |bytes: &[u8]| {
+            *image::load_from_memory(bytes)
+                .unwrap()
+                .to_rgba8()
+                .get_pixel(0, 0)
+        };
+        assert_eq!(pixel(&first), Rgba([255, 0, 0, 255]));
+        assert_eq!(pixel(&second), Rgba([0, 0, 255, 255]))
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn render_batch_reports_every_item_and_keeps_errors_separate() {
+        // arrange
+        let generator = RoboHashGenerator::new();
+        let mut assets = assets([255, 0, 0, 255]);
+        let part = crate::image::encode(&RgbaImage::new(4, 4), Format::Png).unwrap();
+        assets.insert_file(Path::new("./sets/custom/001#b/000#b0.png"), part);
+        assets.insert_file(Path::new("./sets/custom/001#b/001#b1.png"), vec![]);
+        let options = RenderOptions::new()
+            .with_asset_source(assets)
+            .with_set("custom")
+            .with_size(4, 4);
+        let inputs = (0..16)
+            .map(|index| index.to_string())
+            .collect::<Vec<String>>();
+        let expected = inputs
+            .iter()
+            .map(|input| (input.to_owned(), generator.render(input, &options).is_ok()))
+            .collect::<std::collections::BTreeMap<String, bool>>();
+        let items = std::sync::Mutex::new(Vec::new());
+        // act
+        generator.render_batch(&inputs, &options, |item| items.lock().unwrap().push(item));
+        // assert
+        let items = items.into_inner().unwrap();
+        let outcomes = items
+            .iter()
+            .map(|item| (item.input.to_owned(), item.result.is_ok()))
+            .collect::<std::collections::BTreeMap<String, bool>>();
+        assert_eq!(items.len(), inputs.len());
+        assert_eq!(outcomes, expected);
+        assert!(outcomes.values().any(|ok| *ok));
+        assert!(outcomes.values().any(|ok| !*ok))
+    }
+}
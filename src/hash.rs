@@ -1,13 +1,62 @@
 use std::io::{BufReader, Read};
 
 use data_encoding::HEXLOWER;
-use ring::digest::{Context, SHA512};
 
 use crate::error::Error;
 
-pub(crate) fn sha512_digest(string: &str) -> Result<String, Error> {
+const SHA512_BLOCK_SIZE: usize = 128;
+
+#[cfg(not(any(feature = "ring", feature = "sha2", target_arch = "wasm32")))]
+compile_error!("robohash needs a hashing backend, enable the `ring` or `sha2` feature");
+
+#[cfg(all(feature = "ring", not(target_arch = "wasm32")))]
+mod backend {
+    use ring::digest::{Context, SHA512};
+
+    pub(super) struct Sha512(Context);
+
+    impl Sha512 {
+        pub(super) fn new() -> Self {
+            Self(Context::new(&SHA512))
+        }
+
+        pub(super) fn update(&mut self, bytes: &[u8]) {
+            self.0.update(bytes);
+        }
+
+        pub(super) fn finish(self) -> Vec<u8> {
+            self.0.finish().as_ref().to_vec()
+        }
+    }
+}
+
+#[cfg(all(
+    any(feature = "sha2", target_arch = "wasm32"),
+    not(all(feature = "ring", not(target_arch = "wasm32")))
+))]
+mod backend {
+    use sha2::Digest;
+
+    pub(super) struct Sha512(sha2::Sha512);
+
+    impl Sha512 {
+        pub(super) fn new() -> Self {
+            Self(sha2::Sha512::new())
+        }
+
+        pub(super) fn update(&mut self, bytes: &[u8]) {
+            self.0.update(bytes);
+        }
+
+        pub(super) fn finish(self) -> Vec<u8> {
+            self.0.finalize().to_vec()
+        }
+    }
+}
+
+pub fn sha512_digest(string: &str) -> Result<String, Error> {
     let mut reader = BufReader::new(string.as_bytes());
-    let mut context = Context::new(&SHA512);
+    let mut hasher = Hasher::new();
     let mut buffer = [0; 1024];
 
     loop {
@@ -15,14 +64,74 @@ pub(crate) fn sha512_digest(string: &str) -> Result<String, Error> {
         if count == 0 {
             break;
         }
-        context.update(&buffer[..count]);
+        hasher.update(&buffer[..count]);
+    }
+
+    Ok(hasher.finish_hex())
+}
+
+pub(crate) struct Hasher {
+    context: backend::Sha512,
+}
+
+impl Hasher {
+    pub(crate) fn new() -> Self {
+        Self {
+            context: backend::Sha512::new(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.context.update(bytes);
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.context.finish()
+    }
+
+    pub(crate) fn finish_hex(self) -> String {
+        HEXLOWER.encode(&self.finish())
     }
+}
 
-    let digest = context.finish();
-    Ok(HEXLOWER.encode(digest.as_ref()))
+pub(crate) fn hmac_sha512(key: &[u8], message: &[u8]) -> String {
+    let key = if key.len() > SHA512_BLOCK_SIZE {
+        sha512_raw(key)
+    } else {
+        key.to_vec()
+    };
+    let mut block = [0_u8; SHA512_BLOCK_SIZE];
+    block[..key.len()].copy_from_slice(&key);
+    let pad = |byte: u8| block.iter().map(|key| key ^ byte).collect::<Vec<u8>>();
+
+    let mut inner = Hasher::new();
+    inner.update(&pad(0x36));
+    inner.update(message);
+    let mut outer = Hasher::new();
+    outer.update(&pad(0x5c));
+    outer.update(&inner.finish());
+    outer.finish_hex()
 }
 
-pub(crate) fn split_hash(hash: &str, chunks: usize) -> Result<Vec<i64>, Error> {
+fn sha512_raw(bytes: &[u8]) -> Vec<u8> {
+    let mut hasher = Hasher::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+pub(crate) fn sha512_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Hasher::new();
+    hasher.update(bytes);
+    hasher.finish_hex()
+}
+
+pub fn split_hash(hash: &str, chunks: usize) -> Result<Vec<i64>, Error> {
+    if chunks == 0 || chunks > hash.len() {
+        return Err(Error::InvalidArrayIndex(
+            String::from(hash),
+            format!("{chunks} chunks"),
+        ));
+    }
     let mut vector: Vec<i64> = Vec::with_capacity(chunks);
     for i in 0..chunks {
         let block_size = hash.len() / chunks;
@@ -52,6 +161,41 @@ mod tests {
         assert_eq!(hash.unwrap(), expected_hash)
     }
 
+    #[test]
+    fn sha512_bytes_matches_sha512_digest_of_the_same_string() {
+        // arrange
+        let initial_string = "initial_string";
+        // act
+        let hash = sha512_bytes(initial_string.as_bytes());
+        // assert
+        assert_eq!(hash, sha512_digest(initial_string).unwrap())
+    }
+
+    #[test]
+    fn hmac_sha512_matches_the_rfc_4231_test_vectors() {
+        // arrange
+        let long_key = [0xaa; 131];
+        // act
+        let short = hmac_sha512(b"Jefe", b"what do ya want for nothing?");
+        let long = hmac_sha512(
+            &long_key,
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+        );
+        // assert
+        assert_eq!(short, "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737");
+        assert_eq!(long, "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598")
+    }
+
+    #[test]
+    fn split_hash_returns_an_error_for_zero_or_too_many_chunks() {
+        // act
+        let zero = split_hash("92ba", 0);
+        let too_many = split_hash("92ba", 5);
+        // assert
+        assert!(matches!(zero, Err(Error::InvalidArrayIndex(_, _))));
+        assert!(matches!(too_many, Err(Error::InvalidArrayIndex(_, _))))
+    }
+
     #[test]
     fn split_hash_returns_given_number_of_chunks_of_a_string() {
         // arrange
@@ -1,59 +1,376 @@
+use std::collections::HashMap;
 use std::io::Cursor;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
 
-use image::{imageops, DynamicImage, ImageBuffer, Rgba, RgbaImage};
+use image::{imageops, DynamicImage, RgbaImage};
 
+use crate::asset::{self, AssetSource};
+use crate::background::Backdrop;
 use crate::error::Error;
+use crate::fill;
+use crate::format::Format;
+use crate::mask::Mask;
+use crate::masking::{self, Border};
 
 pub(crate) fn build_robo_hash_image(
-    robo_parts: &Vec<String>,
-    background: &Option<String>,
+    robo_parts: &[String],
+    backdrop: &Backdrop,
     width: u32,
     height: u32,
+    mask: &Option<Mask>,
+    border: &Option<Border>,
+    loader: &Loader,
 ) -> Result<RgbaImage, Error> {
-    let mut base_image = image::ImageBuffer::new(width, height);
-    if let Some(background) = background {
-        append_to_image(&mut base_image, background, width, height)?;
+    let mut base_image = match backdrop {
+        Backdrop::HashDerived {
+            hue_seed,
+            offset_seed,
+        } => {
+            let robot = compose(robo_parts, image::ImageBuffer::new(width, height), loader)?;
+            let (from, to) = fill::hash_derived_colours(*hue_seed, *offset_seed, &robot);
+            let gradient = Backdrop::LinearGradient {
+                from,
+                to,
+                angle: 90.0,
+            };
+            let mut base_image = image::ImageBuffer::new(width, height);
+            fill::fill(&mut base_image, &gradient);
+            imageops::overlay(&mut base_image, &robot, 0, 0);
+            base_image
+        }
+        backdrop => {
+            let mut base_image = image::ImageBuffer::new(width, height);
+            fill::fill(&mut base_image, backdrop);
+            if let Backdrop::Image(background) = backdrop {
+                let fingerprint = loader
+                    .cache
+                    .as_ref()
+                    .and_then(|cache| cache.background_fingerprint);
+                append_to_image(
+                    &mut base_image,
+                    background,
+                    fingerprint,
+                    width,
+                    height,
+                    loader,
+                )?;
+            }
+            compose(robo_parts, base_image, loader)?
+        }
+    };
+    if let Some(mask) = mask {
+        masking::apply_mask(&mut base_image, mask, border, loader.source)?;
     }
+    Ok(base_image)
+}
+
+fn compose(
+    robo_parts: &[String],
+    mut base_image: RgbaImage,
+    loader: &Loader,
+) -> Result<RgbaImage, Error> {
+    let (width, height) = base_image.dimensions();
+    let fingerprint = loader
+        .cache
+        .as_ref()
+        .and_then(|cache| cache.set_fingerprint);
     robo_parts
         .iter()
         .try_for_each(|image_path| -> Result<(), Error> {
-            append_to_image(&mut base_image, image_path, width, height)?;
+            append_to_image(
+                &mut base_image,
+                image_path,
+                fingerprint,
+                width,
+                height,
+                loader,
+            )?;
             Ok(())
         })?;
     Ok(base_image)
 }
 
 fn append_to_image(
-    base_image: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
-    image_path: &String,
+    base_image: &mut RgbaImage,
+    image_path: &str,
+    fingerprint: Option<&str>,
     width: u32,
     height: u32,
+    loader: &Loader,
 ) -> Result<(), Error> {
-    let image = try_open_image(image_path)?;
-    let mut image = imageops::resize(&image, width, height, imageops::FilterType::Lanczos3);
-    imageops::overlay(base_image, &mut image, 0, 0);
+    let image = match &loader.cache {
+        Some(cache) => {
+            let key = PartKey::new(cache.source, fingerprint, image_path, width, height);
+            cache.parts.get_or_load(cache.source, key)?
+        }
+        None => Arc::new(load_resized(loader.source, image_path, width, height)?),
+    };
+    imageops::overlay(base_image, image.as_ref(), 0, 0);
     Ok(())
 }
 
-fn try_open_image(image_path: &String) -> Result<DynamicImage, Error> {
-    match image::open(image_path) {
+fn load_resized(
+    source: &dyn AssetSource,
+    image_path: &str,
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage, Error> {
+    let image = try_open_image(source, image_path)?;
+    Ok(imageops::resize(
+        &image,
+        width,
+        height,
+        imageops::FilterType::Lanczos3,
+    ))
+}
+
+pub(crate) struct Loader<'a> {
+    pub(crate) source: &'a dyn AssetSource,
+    pub(crate) cache: Option<CacheScope<'a>>,
+}
+
+// Parts are cached per asset source and catalogue fingerprint, so sources that share paths
+// never hand each other their pixels.
+pub(crate) struct CacheScope<'a> {
+    pub(crate) parts: &'a PartCache,
+    pub(crate) source: &'a Arc<dyn AssetSource>,
+    pub(crate) set_fingerprint: Option<&'a str>,
+    pub(crate) background_fingerprint: Option<&'a str>,
+}
+
+const PART_CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct PartKey {
+    source: usize,
+    fingerprint: Option<String>,
+    path: String,
+    width: u32,
+    height: u32,
+}
+
+impl PartKey {
+    fn new(
+        source: &Arc<dyn AssetSource>,
+        fingerprint: Option<&str>,
+        path: &str,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            source: asset::source_identity(source),
+            fingerprint: fingerprint.map(String::from),
+            path: String::from(path),
+            width,
+            height,
+        }
+    }
+}
+
+// Holding a weak reference keeps the source's allocation, and so its address, from being reused
+// by another source while the part is cached.
+#[derive(Debug)]
+struct CachedPart {
+    part: Arc<RgbaImage>,
+    last_used: u64,
+    _source: Weak<dyn AssetSource>,
+}
+
+#[derive(Debug)]
+pub(crate) struct PartCache {
+    max_bytes: u64,
+    parts: Mutex<CachedParts>,
+}
+
+#[derive(Debug, Default)]
+struct CachedParts {
+    entries: HashMap<PartKey, CachedPart>,
+    total_bytes: u64,
+    clock: u64,
+}
+
+impl Default for PartCache {
+    fn default() -> Self {
+        Self::new(PART_CACHE_MAX_BYTES)
+    }
+}
+
+impl PartCache {
+    pub(crate) fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            parts: Mutex::default(),
+        }
+    }
+
+    fn get_or_load(
+        &self,
+        source: &Arc<dyn AssetSource>,
+        key: PartKey,
+    ) -> Result<Arc<RgbaImage>, Error> {
+        if let Some(part) = self.lock().get(&key) {
+            return Ok(part);
+        }
+        let part = load_resized(source.as_ref(), &key.path, key.width, key.height)?;
+        let part = Arc::new(part);
+        let cached = CachedPart {
+            part: Arc::clone(&part),
+            last_used: 0,
+            _source: Arc::downgrade(source),
+        };
+        self.lock().insert(key, cached, self.max_bytes);
+        Ok(part)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CachedParts> {
+        match self.parts.lock() {
+            Ok(parts) => parts,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl CachedParts {
+    fn get(&mut self, key: &PartKey) -> Option<Arc<RgbaImage>> {
+        self.clock += 1;
+        let cached = self.entries.get_mut(key)?;
+        cached.last_used = self.clock;
+        Some(Arc::clone(&cached.part))
+    }
+
+    fn insert(&mut self, key: PartKey, mut cached: CachedPart, max_bytes: u64) {
+        let bytes = cached.part.as_raw().len() as u64;
+        if bytes > max_bytes {
+            return;
+        }
+        self.clock += 1;
+        cached.last_used = self.clock;
+        if let Some(replaced) = self.entries.insert(key, cached) {
+            self.total_bytes -= replaced.part.as_raw().len() as u64;
+        }
+        self.total_bytes += bytes;
+        while self.total_bytes > max_bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.to_owned());
+            match oldest.and_then(|oldest| self.entries.remove(&oldest)) {
+                Some(evicted) => self.total_bytes -= evicted.part.as_raw().len() as u64,
+                None => break,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn keys(&self) -> Vec<(&str, u32, u32)> {
+        let mut keys = self
+            .entries
+            .keys()
+            .map(|key| (key.path.as_str(), key.width, key.height))
+            .collect::<Vec<(&str, u32, u32)>>();
+        keys.sort();
+        keys
+    }
+}
+
+pub(crate) fn try_open_image(
+    source: &dyn AssetSource,
+    image_path: &str,
+) -> Result<DynamicImage, Error> {
+    let bytes = match source.read(Path::new(image_path)) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(Error::ImageOpenFailed(format!("{e:#?}"))),
+    };
+    match image::load_from_memory(&bytes) {
         Ok(image) => Ok(image),
         Err(e) => Err(Error::ImageOpenFailed(format!("{e:#?}"))),
     }
 }
 
-pub(crate) fn to_base_64(image: &RgbaImage) -> Result<String, Error> {
+pub(crate) fn resize_variants(image: &RgbaImage, sizes: &[(u32, u32)]) -> Vec<RgbaImage> {
+    let mut order = (0..sizes.len()).collect::<Vec<usize>>();
+    order.sort_by(|a, b| sizes[*b].0.cmp(&sizes[*a].0));
+
+    let mut rendered: Vec<RgbaImage> = Vec::with_capacity(sizes.len());
+    let mut variants: Vec<Option<RgbaImage>> = vec![None; sizes.len()];
+    for index in order {
+        let (width, height) = sizes[index];
+        let source = rendered
+            .iter()
+            .rev()
+            .find(|candidate| candidate.width() >= width && candidate.height() >= height)
+            .unwrap_or(image);
+        let variant = if source.dimensions() == (width, height) {
+            source.clone()
+        } else {
+            imageops::resize(source, width, height, imageops::FilterType::Lanczos3)
+        };
+        rendered.push(variant.clone());
+        variants[index] = Some(variant);
+    }
+    variants.into_iter().flatten().collect()
+}
+
+pub(crate) fn encode(image: &RgbaImage, format: Format) -> Result<Vec<u8>, Error> {
     let mut bytes: Vec<u8> = Vec::new();
-    image.write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
-    Ok(base64::encode(&bytes))
+    match format {
+        Format::Png => {
+            image.write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+        }
+        Format::Jpeg { quality } => {
+            let image = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+            image.write_to(
+                &mut Cursor::new(&mut bytes),
+                image::ImageOutputFormat::Jpeg(quality),
+            )?;
+        }
+    }
+    Ok(bytes)
 }
 
-#[cfg(test)]
-pub(crate) mod tests {
-    use std::fs::File;
-    use std::io::Read;
+pub(crate) fn to_base_64(image: &RgbaImage, format: Format) -> Result<String, Error> {
+    let bytes = encode(image, format)?;
+    Ok(base64::encode(bytes))
+}
 
+#[cfg(test)]
+mod tests {
     use super::*;
+    use crate::asset::FileSystemAssetSource;
+    use crate::testing;
+
+    const LOADER: Loader = Loader {
+        source: &FileSystemAssetSource,
+        cache: None,
+    };
+
+    #[test]
+    fn part_cache_evicts_the_least_recently_used_parts_over_its_limit() {
+        // arrange
+        let part = encode(&RgbaImage::new(4, 4), Format::Png).unwrap();
+        let mut assets = crate::asset::MemoryAssetSource::new();
+        for name in ["a.png", "b.png", "c.png", "large.png"] {
+            assets.insert_file(Path::new(name), part.clone());
+        }
+        let assets: Arc<dyn AssetSource> = Arc::new(assets);
+        let cache = PartCache::new(2 * 4 * 4 * 4);
+        let load = |name: &str, size| {
+            let key = PartKey::new(&assets, None, name, size, size);
+            cache.get_or_load(&assets, key).unwrap()
+        };
+        // act
+        load("a.png", 4);
+        load("b.png", 4);
+        load("a.png", 4);
+        load("c.png", 4);
+        load("large.png", 8);
+        // assert
+        let parts = cache.lock();
+        assert_eq!(parts.keys(), vec![("a.png", 4, 4), ("c.png", 4, 4)]);
+        assert_eq!(parts.total_bytes, 2 * 4 * 4 * 4)
+    }
 
     #[test]
     fn build_robo_hash_image_returns_built_image_of_parts() {
@@ -66,13 +383,114 @@ pub(crate) mod tests {
             String::from("./sets/set4/004#04accessories/003#accessory3.png"),
         ];
         // act
-        let robo_hash = build_robo_hash_image(&robo_parts, &None, 512, 512);
+        let robo_hash = build_robo_hash_image(
+            &robo_parts,
+            &Backdrop::None,
+            512,
+            512,
+            &None,
+            &None,
+            &LOADER,
+        );
         // assert
         assert!(robo_hash.is_ok())
     }
 
     #[test]
-    fn to_base64_converts_image_to_base64_string() {
+    fn build_robo_hash_image_applies_mask_after_composition() {
+        // arrange
+        let robo_parts = vec![String::from("./sets/set4/000#00body/003#body3.png")];
+        let background = Backdrop::Image(String::from("./backgrounds/bg1/000#robotBG-11.png"));
+        // act
+        let robo_hash = build_robo_hash_image(
+            &robo_parts,
+            &background,
+            64,
+            64,
+            &Some(Mask::Circle),
+            &None,
+            &LOADER,
+        )
+        .unwrap();
+        // assert
+        assert_eq!(robo_hash.get_pixel(0, 0)[3], 0);
+        assert_eq!(robo_hash.get_pixel(32, 32)[3], 255)
+    }
+
+    #[test]
+    fn build_robo_hash_image_fills_hash_derived_background_behind_robot() {
+        // arrange
+        let robo_parts = vec![String::from("./sets/set4/000#00body/003#body3.png")];
+        let backdrop = Backdrop::HashDerived {
+            hue_seed: 200,
+            offset_seed: 3,
+        };
+        // act
+        let robo_hash =
+            build_robo_hash_image(&robo_parts, &backdrop, 64, 64, &None, &None, &LOADER);
+        // assert
+        let robo_hash = robo_hash.unwrap();
+        assert_eq!(robo_hash.get_pixel(0, 0)[3], 255);
+        assert_eq!(robo_hash.get_pixel(63, 63)[3], 255)
+    }
+
+    #[test]
+    fn build_robo_hash_image_with_cache_reuses_resized_parts() {
+        // arrange
+        let robo_parts = vec![
+            String::from("./sets/set4/000#00body/003#body3.png"),
+            String::from("./sets/set4/001#01fur/006#fur6.png"),
+        ];
+        let cache = PartCache::default();
+        let source: Arc<dyn AssetSource> = Arc::new(FileSystemAssetSource);
+        let loader = Loader {
+            source: source.as_ref(),
+            cache: Some(CacheScope {
+                parts: &cache,
+                source: &source,
+                set_fingerprint: None,
+                background_fingerprint: None,
+            }),
+        };
+        // act
+        let first =
+            build_robo_hash_image(&robo_parts, &Backdrop::None, 64, 64, &None, &None, &loader)
+                .unwrap();
+        let second =
+            build_robo_hash_image(&robo_parts, &Backdrop::None, 64, 64, &None, &None, &loader)
+                .unwrap();
+        // assert
+        assert_eq!(cache.lock().entries.len(), 2);
+        assert_eq!(first, second)
+    }
+
+    #[test]
+    fn resize_variants_returns_images_in_requested_order() {
+        // arrange
+        let image = RgbaImage::new(64, 64);
+        let sizes = vec![(16, 16), (64, 64), (32, 32)];
+        // act
+        let variants = resize_variants(&image, &sizes);
+        // assert
+        let dimensions = variants
+            .iter()
+            .map(|variant| variant.dimensions())
+            .collect::<Vec<(u32, u32)>>();
+        assert_eq!(dimensions, sizes)
+    }
+
+    #[test]
+    fn encode_jpeg_writes_a_jpeg_stream() {
+        // arrange
+        let image = RgbaImage::from_pixel(8, 8, image::Rgba([10, 20, 30, 255]));
+        // act
+        let bytes = encode(&image, Format::Jpeg { quality: 80 }).unwrap();
+        // assert
+        assert_eq!(&bytes[..2], &[0xFF, 0xD8])
+    }
+
+    #[test]
+    fn build_robo_hash_image_matches_golden_image() {
         // arrange
         let robo_parts = vec![
             String::from("./sets/set4/000#00body/003#body3.png"),
@@ -81,20 +499,33 @@ pub(crate) mod tests {
             String::from("./sets/set4/003#03mouth/007#mouth7.png"),
             String::from("./sets/set4/004#04accessories/003#accessory3.png"),
         ];
-        let expected_base64 = load_base64_string_image_resources("image");
-        let robo_hash = build_robo_hash_image(&robo_parts, &None, 512, 512).unwrap();
         // act
-        let base64_string = to_base_64(&robo_hash);
+        let robo_hash = build_robo_hash_image(
+            &robo_parts,
+            &Backdrop::None,
+            512,
+            512,
+            &None,
+            &None,
+            &LOADER,
+        )
+        .unwrap();
         // assert
-        assert!(base64_string.is_ok());
-        assert_eq!(base64_string.unwrap(), expected_base64)
+        let bytes = encode(&robo_hash, Format::Png).unwrap();
+        testing::assert_golden_image(&bytes, "./test_resources/image.png", 2)
     }
 
-    pub(crate) fn load_base64_string_image_resources(filename: &str) -> String {
-        let mut file_contents = String::new();
-        let file_location = format!("./test_resources/{}.txt", filename);
-        let mut file = File::open(&file_location).unwrap();
-        file.read_to_string(&mut file_contents).unwrap();
-        file_contents
+    #[test]
+    fn to_base64_encodes_the_image_pixels() {
+        // arrange
+        let image = RgbaImage::from_pixel(8, 8, image::Rgba([10, 20, 30, 255]));
+        let expected = encode(&image, Format::Png).unwrap();
+        // act
+        let base64_string = to_base_64(&image, Format::Png).unwrap();
+        // assert
+        let decoded = base64::decode(base64_string).unwrap();
+        assert!(testing::compare_images(&decoded, &expected, 0)
+            .unwrap()
+            .matches())
     }
 }
@@ -1,114 +1,436 @@
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+#[cfg(feature = "render")]
+use ::image::RgbaImage;
+
+pub use crate::analysis::{collision_groups, collision_probability, combinations};
+pub use crate::asset::{
+    AssetSource, EmbeddedAssetSource, FileSystemAssetSource, MemoryAssetSource, SymlinkPolicy,
+};
+#[cfg(feature = "async")]
+pub use crate::asset::{AsyncAssetSource, TokioFileSystemAssetSource};
+use crate::background::Backdrop;
+pub use crate::background::{Background, Rgba};
+pub use crate::catalogue::Catalogue;
+pub use crate::config::{Profile, RoboHashConfig};
+#[cfg(feature = "contact-sheet")]
+pub use crate::contact_sheet::contact_sheet;
 use crate::error::Error;
+pub use crate::format::Format;
+#[cfg(feature = "parallel")]
+pub use crate::generator::BatchItem;
+#[cfg(feature = "render")]
+pub use crate::generator::RoboHashGenerator;
+pub use crate::hash::{sha512_digest, split_hash};
+#[cfg(feature = "render")]
+use crate::image::{CacheScope, Loader, PartCache};
+pub use crate::manifest::{Manifest, Resolution};
+pub use crate::mask::Mask;
+#[cfg(feature = "render")]
+use crate::masking::Border;
+use crate::options::HashInput;
+pub use crate::options::RenderOptions;
+#[cfg(feature = "render")]
+pub use crate::registry::{ProfileRegistry, Tenant};
+pub use crate::render_cache::RenderCache;
+pub use crate::selection::Algorithm;
+use crate::selection::{Selector, Slot};
+use crate::set_definition::Selection;
+pub use crate::set_definition::{CategoryDefinition, SetDefinition, SizeDefinition};
+#[cfg(feature = "render")]
+pub use crate::srcset::{Variant, Variants};
 
+mod analysis;
+mod asset;
+#[cfg(feature = "async")]
+mod async_render;
+mod background;
+mod catalogue;
+mod config;
+#[cfg(feature = "contact-sheet")]
+mod contact_sheet;
 pub mod error;
+#[cfg(feature = "render")]
+mod fill;
+mod format;
+#[cfg(feature = "render")]
+mod generator;
 mod hash;
+#[cfg(feature = "render")]
 mod image;
+mod manifest;
+mod mask;
+#[cfg(feature = "render")]
+mod masking;
 mod materials;
+mod options;
+#[cfg(feature = "render")]
+mod palette;
+#[cfg(feature = "render")]
+mod registry;
+mod render_cache;
+mod selection;
+mod set_definition;
+#[cfg(feature = "render")]
+mod srcset;
+#[cfg(all(feature = "render", any(test, feature = "testing")))]
+pub mod testing;
 
 const SET_DEFAULT: &str = "set1";
+#[cfg(feature = "render")]
+const MAX_IMAGE_PIXELS: u64 = 8192 * 8192;
 
-pub struct RoboHashBuilder<'a> {
-    text: &'a str,
-    colour: Option<String>,
-    image_size: ImageSize,
-    set: String,
-    set_root: String,
-    background_set: Option<String>,
-    background_root: String,
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RoboHashBuilder {
+    text: String,
+    options: RenderOptions,
 }
 
-impl<'a> RoboHashBuilder<'a> {
-    pub fn new(text: &'a str) -> Self {
-        let colour = None;
-        let image_size = ImageSize::default();
-        let set = String::from(SET_DEFAULT);
-        let set_root = String::from("./sets");
-        let background_set = None;
-        let background_root = String::from("./backgrounds");
+impl RoboHashBuilder {
+    pub fn new(text: &str) -> Self {
+        let options = RenderOptions::default();
         Self {
-            text,
-            colour,
-            image_size,
-            set,
-            set_root,
-            background_set,
-            background_root,
+            text: String::from(text),
+            options,
         }
     }
 
-    pub fn with_set(mut self, set: &str) -> RoboHashBuilder<'a> {
-        self.set = String::from(set);
+    pub fn with_options(mut self, options: RenderOptions) -> RoboHashBuilder {
+        self.options = options;
+        self
+    }
+
+    pub fn with_set(mut self, set: &str) -> RoboHashBuilder {
+        self.options = self.options.with_set(set);
+        self
+    }
+
+    pub fn with_set_location(mut self, set_location: &str) -> RoboHashBuilder {
+        self.options = self.options.with_set_location(set_location);
+        self
+    }
+
+    pub fn with_background_set(mut self, background_set: &str) -> RoboHashBuilder {
+        self.options = self.options.with_background_set(background_set);
+        self
+    }
+
+    pub fn with_background(mut self, background: Background) -> RoboHashBuilder {
+        self.options = self.options.with_background(background);
+        self
+    }
+
+    pub fn with_background_location(mut self, background_location: &str) -> RoboHashBuilder {
+        self.options = self.options.with_background_location(background_location);
+        self
+    }
+
+    pub fn with_colour(mut self, colour: &str) -> RoboHashBuilder {
+        self.options = self.options.with_colour(colour);
         self
     }
 
-    pub fn with_set_location(mut self, set_location: &str) -> RoboHashBuilder<'a> {
-        self.set_root = String::from(set_location);
+    pub fn with_size(mut self, width: u32, height: u32) -> RoboHashBuilder {
+        self.options = self.options.with_size(width, height);
         self
     }
 
-    pub fn with_background_set(mut self, background_set: &str) -> RoboHashBuilder<'a> {
-        self.background_set = Some(String::from(background_set));
+    pub fn with_mask(mut self, mask: Mask) -> RoboHashBuilder {
+        self.options = self.options.with_mask(mask);
         self
     }
 
-    pub fn with_background_location(mut self, background_location: &str) -> RoboHashBuilder<'a> {
-        self.background_root = String::from(background_location);
+    pub fn with_mask_location(mut self, mask_location: &str) -> RoboHashBuilder {
+        self.options = self.options.with_mask_location(mask_location);
         self
     }
 
-    pub fn with_colour(mut self, colour: &str) -> RoboHashBuilder<'a> {
-        self.colour = Some(String::from(colour));
+    pub fn with_mask_border(mut self, width: u32) -> RoboHashBuilder {
+        self.options = self.options.with_mask_border(width);
         self
     }
 
-    pub fn with_size(mut self, width: u32, height: u32) -> RoboHashBuilder<'a> {
-        self.image_size = ImageSize { width, height };
+    pub fn with_format(mut self, format: Format) -> RoboHashBuilder {
+        self.options = self.options.with_format(format);
+        self
+    }
+
+    pub fn with_symlink_policy(mut self, symlinks: SymlinkPolicy) -> RoboHashBuilder {
+        self.options = self.options.with_symlink_policy(symlinks);
+        self
+    }
+
+    pub fn with_catalogue(mut self, catalogue: Arc<Catalogue>) -> RoboHashBuilder {
+        self.options = self.options.with_catalogue(catalogue);
+        self
+    }
+
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> RoboHashBuilder {
+        self.options = self.options.with_algorithm(algorithm);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> RoboHashBuilder {
+        self.options = self.options.with_seed(seed);
+        self
+    }
+
+    pub fn with_hash_array(mut self, hash_array: &[i64]) -> RoboHashBuilder {
+        self.options = self.options.with_hash_array(hash_array);
+        self
+    }
+
+    pub fn with_key(mut self, key: &[u8]) -> RoboHashBuilder {
+        self.options = self.options.with_key(key);
+        self
+    }
+
+    pub fn with_parts(mut self, parts: &[(&str, usize)]) -> RoboHashBuilder {
+        self.options = self.options.with_parts(parts);
+        self
+    }
+
+    pub fn with_asset_source<S: AssetSource + 'static>(mut self, source: S) -> RoboHashBuilder {
+        self.options = self.options.with_asset_source(source);
         self
     }
 
     pub fn build(&self) -> Result<RoboHash, Error> {
-        let hash_array_chunks = 11;
-        let hash = hash::sha512_digest(&self.text)?;
-        let hash_array = hash::split_hash(&hash, hash_array_chunks)?;
-        let colour = colour_selection(&hash_array, &self.colour, &self.set, &self.set_root)?;
-        let set = self.set_with_colour(colour);
-        let sets_root = self.set_root.to_owned();
-        let background_set = self.background_set.to_owned();
-        let background_root = self.background_root.to_owned();
+        let options = &self.options;
+        let (hash, hash_array) = self.hash()?;
+        let selector =
+            Selector::new(options.algorithm, &hash, &hash_array).with_parts(&options.parts);
+        let asset_source = Arc::clone(&options.asset_source);
+        materials::asset_directory(
+            asset_source.as_ref(),
+            options
+                .catalogue
+                .as_ref()
+                .map(|catalogue| catalogue.set_fingerprint(&options.set).is_some()),
+            &options.set_root,
+            &options.set,
+        )?;
+        let definition = SetDefinition::load(
+            asset_source.as_ref(),
+            options.symlinks,
+            &options.set_root,
+            &options.set,
+        )?;
+        let set = match &definition {
+            Some(definition) => {
+                match definition.colour(&selector, &options.set, &options.colour)? {
+                    Some(colour) => format!("{}/{}", options.set, colour),
+                    None => options.set.to_owned(),
+                }
+            }
+            None => {
+                let colour = colour_selection(
+                    asset_source.as_ref(),
+                    &selector,
+                    &options.colour,
+                    &options.set,
+                    &options.set_root,
+                )?;
+                self.set_with_colour(colour)
+            }
+        };
+        materials::check_name(&set)?;
+        if let Some(Mask::Custom(mask)) = &options.mask {
+            materials::check_name(mask)?;
+        }
+        let image_size = options
+            .image_size
+            .or_else(|| definition.as_ref().and_then(SetDefinition::image_size))
+            .unwrap_or_else(ImageSize::default);
+        let sets_root = options.set_root.to_owned();
+        let background = options.background.to_owned();
+        let background_root = options.background_root.to_owned();
+        let mask = options.mask.to_owned();
+        let mask_root = options.mask_root.to_owned();
+        let mask_border = options.mask_border;
+        let format = options.format;
+        let symlinks = options.symlinks;
+        let catalogue = options.catalogue.to_owned();
+        let algorithm = options.algorithm;
+        let parts = options.parts.to_owned();
 
         Ok(RoboHash {
-            image_size: self.image_size,
+            image_size,
+            digest: hash,
             hash_array,
             set,
+            definition,
             sets_root,
-            background_set,
+            background,
             background_root,
+            mask,
+            mask_root,
+            mask_border,
+            format,
+            asset_source,
+            symlinks,
+            catalogue,
+            algorithm,
+            parts,
         })
     }
 
+    fn hash(&self) -> Result<(String, Vec<i64>), Error> {
+        let hash_array_chunks = 11;
+        match &self.options.hash_input {
+            HashInput::Text => {
+                let hash = hash::sha512_digest(&self.text)?;
+                let hash_array = hash::split_hash(&hash, hash_array_chunks)?;
+                Ok((hash, hash_array))
+            }
+            HashInput::Seed(seed) => {
+                let hash = hash::sha512_bytes(&seed.to_be_bytes());
+                let hash_array = hash::split_hash(&hash, hash_array_chunks)?;
+                Ok((hash, hash_array))
+            }
+            HashInput::HashArray(hash_array) => {
+                let bytes = hash_array
+                    .iter()
+                    .flat_map(|value| value.to_be_bytes())
+                    .collect::<Vec<u8>>();
+                Ok((hash::sha512_bytes(&bytes), hash_array.to_owned()))
+            }
+            HashInput::Keyed(key) => {
+                let hash = hash::hmac_sha512(&key.0, self.text.as_bytes());
+                let hash_array = hash::split_hash(&hash, hash_array_chunks)?;
+                Ok((hash, hash_array))
+            }
+        }
+    }
+
     fn set_with_colour(&self, colour: Option<String>) -> String {
-        match self.set.as_str() {
+        let set = self.options.set.as_str();
+        match set {
             SET_DEFAULT => match colour {
-                Some(colour) => format!("{}/{}", &self.set.as_str(), colour.as_str()),
-                None => String::from(self.set.as_str()),
+                Some(colour) => format!("{}/{}", set, colour.as_str()),
+                None => String::from(set),
             },
-            _ => String::from(self.set.as_str()),
+            _ => String::from(set),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoboHash {
     image_size: ImageSize,
+    digest: String,
     hash_array: Vec<i64>,
     set: String,
+    definition: Option<SetDefinition>,
     sets_root: String,
-    background_set: Option<String>,
+    background: Background,
     background_root: String,
+    mask: Option<Mask>,
+    #[serde(default = "default_mask_root")]
+    mask_root: String,
+    mask_border: Option<u32>,
+    format: Format,
+    #[serde(skip, default = "asset::default_asset_source")]
+    asset_source: Arc<dyn AssetSource>,
+    #[serde(default)]
+    symlinks: SymlinkPolicy,
+    #[serde(serialize_with = "catalogue_fingerprint", skip_deserializing)]
+    catalogue: Option<Arc<Catalogue>>,
+    algorithm: Algorithm,
+    parts: BTreeMap<String, usize>,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct ImageSize {
+impl PartialEq for RoboHash {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            image_size,
+            digest,
+            hash_array,
+            set,
+            definition,
+            sets_root,
+            background,
+            background_root,
+            mask,
+            mask_root,
+            mask_border,
+            format,
+            asset_source,
+            symlinks,
+            catalogue,
+            algorithm,
+            parts,
+        } = self;
+        *image_size == other.image_size
+            && *digest == other.digest
+            && *hash_array == other.hash_array
+            && *set == other.set
+            && *definition == other.definition
+            && *sets_root == other.sets_root
+            && *background == other.background
+            && *background_root == other.background_root
+            && *mask == other.mask
+            && *mask_root == other.mask_root
+            && *mask_border == other.mask_border
+            && *format == other.format
+            && asset::source_identity(asset_source) == asset::source_identity(&other.asset_source)
+            && *symlinks == other.symlinks
+            && *catalogue == other.catalogue
+            && *algorithm == other.algorithm
+            && *parts == other.parts
+    }
+}
+
+impl Eq for RoboHash {}
+
+impl Hash for RoboHash {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            image_size,
+            digest,
+            hash_array,
+            set,
+            definition,
+            sets_root,
+            background,
+            background_root,
+            mask,
+            mask_root,
+            mask_border,
+            format,
+            asset_source,
+            symlinks,
+            catalogue,
+            algorithm,
+            parts,
+        } = self;
+        image_size.hash(state);
+        digest.hash(state);
+        hash_array.hash(state);
+        set.hash(state);
+        definition.hash(state);
+        sets_root.hash(state);
+        background.hash(state);
+        background_root.hash(state);
+        mask.hash(state);
+        mask_root.hash(state);
+        mask_border.hash(state);
+        format.hash(state);
+        asset::source_identity(asset_source).hash(state);
+        symlinks.hash(state);
+        catalogue.hash(state);
+        algorithm.hash(state);
+        parts.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct ImageSize {
     width: u32,
     height: u32,
 }
@@ -120,28 +442,344 @@ impl ImageSize {
             height: 1024,
         }
     }
+
+    #[cfg(feature = "render")]
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        let pixels = self.width as u64 * self.height as u64;
+        if pixels == 0 || pixels > MAX_IMAGE_PIXELS {
+            return Err(Error::InvalidImageSize(self.width, self.height));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "render")]
+    fn scaled_to_width(&self, width: u32) -> Result<Self, Error> {
+        let height = (width as u64 * self.height as u64 / self.width.max(1) as u64) as u32;
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidImageSize(width, height));
+        }
+        Ok(Self { width, height })
+    }
 }
 
 impl RoboHash {
+    pub fn with_asset_source<S: AssetSource + 'static>(mut self, source: S) -> Self {
+        self.asset_source = Arc::new(source);
+        self
+    }
+
+    #[cfg(feature = "render")]
+    pub fn assemble(&self) -> Result<Vec<u8>, Error> {
+        let image = self.assemble_image(self.image_size, None)?;
+        image::encode(&image, self.format)
+    }
+
+    #[cfg(feature = "render")]
     pub fn assemble_base64(&self) -> Result<String, Error> {
+        let image = self.assemble_image(self.image_size, None)?;
+        let base64 = image::to_base_64(&image, self.format)?;
+        Ok(base64)
+    }
+
+    #[cfg(feature = "render")]
+    pub(crate) fn assemble_cached(&self, cache: &PartCache) -> Result<Vec<u8>, Error> {
+        let image = self.assemble_image(self.image_size, Some(cache))?;
+        image::encode(&image, self.format)
+    }
+
+    #[cfg(feature = "render")]
+    pub(crate) fn assemble_base64_cached(&self, cache: &PartCache) -> Result<String, Error> {
+        let image = self.assemble_image(self.image_size, Some(cache))?;
+        let base64 = image::to_base_64(&image, self.format)?;
+        Ok(base64)
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn cache_key(&self) -> Result<String, Error> {
+        if self.is_missing_required_data() {
+            return Err(Error::RoboHashMissingRequiredData);
+        }
+        let key = [
+            self.digest.to_owned(),
+            self.set.to_owned(),
+            format!("{:?}", self.background),
+            format!("{}x{}", self.image_size.width, self.image_size.height),
+            format!("{:?}", self.format),
+            format!("{:?}", self.mask),
+            format!("{:?}", self.mask_border),
+            format!("{:?}", self.algorithm),
+            format!("{:?}", self.parts),
+            self.asset_version()?,
+        ]
+        .join("\n");
+        let key = hash::sha512_digest(&key)?;
+        Ok(String::from(&key[..32]))
+    }
+
+    pub fn set_fingerprint(&self) -> Result<String, Error> {
+        if let Some(fingerprint) = self
+            .catalogue
+            .as_ref()
+            .and_then(|catalogue| catalogue.set_fingerprint(&self.set))
+        {
+            return Ok(String::from(fingerprint));
+        }
+        let directory = self.set_directory(self.asset_source.as_ref())?;
+        catalogue::fingerprint(
+            self.asset_source.as_ref(),
+            self.symlinks,
+            &self.sets_root,
+            &directory,
+        )
+    }
+
+    pub fn background_fingerprint(&self) -> Result<Option<String>, Error> {
+        let set = match &self.background {
+            Background::Set(set) => set,
+            _ => return Ok(None),
+        };
+        if let Some(fingerprint) = self
+            .catalogue
+            .as_ref()
+            .and_then(|catalogue| catalogue.background_fingerprint(set))
+        {
+            return Ok(Some(String::from(fingerprint)));
+        }
+        let directory = self.background_directory(self.asset_source.as_ref(), set)?;
+        Ok(Some(catalogue::fingerprint(
+            self.asset_source.as_ref(),
+            self.symlinks,
+            &self.background_root,
+            &directory,
+        )?))
+    }
+
+    pub fn resolve(&self) -> Result<Resolution, Error> {
+        self.resolve_in(self.asset_source.as_ref())
+    }
+
+    pub fn manifest(&self) -> Result<Manifest, Error> {
+        let resolution = self.resolve()?;
+        Ok(Manifest {
+            set: resolution.set,
+            algorithm: self.algorithm,
+            parts: resolution.parts,
+            omitted_categories: resolution.omitted_categories,
+            background: resolution.background,
+            set_fingerprint: self.set_fingerprint()?,
+            background_fingerprint: self.background_fingerprint()?,
+        })
+    }
+
+    fn resolve_in(&self, source: &dyn AssetSource) -> Result<Resolution, Error> {
+        if self.is_missing_required_data() {
+            return Err(Error::RoboHashMissingRequiredData);
+        }
+        let selection = self.files_in_set(source)?;
+        let background = match self.backdrop(source)? {
+            Backdrop::Image(background) => Some(background),
+            _ => None,
+        };
+        Ok(Resolution {
+            set: self.set.to_owned(),
+            parts: selection.parts,
+            omitted_categories: selection.omitted,
+            background,
+        })
+    }
+
+    fn asset_version(&self) -> Result<String, Error> {
+        if self.catalogue.is_none() {
+            return Err(Error::CatalogueRequired(String::from(
+                "compute a cache key",
+            )));
+        }
+        let background_fingerprint = self.background_fingerprint()?.unwrap_or_default();
+        Ok(format!(
+            "{}:{}",
+            self.set_fingerprint()?,
+            background_fingerprint
+        ))
+    }
+
+    #[cfg(feature = "render")]
+    pub fn assemble_sizes(&self, sizes: &[u32]) -> Result<Variants, Error> {
+        let image_sizes = sizes
+            .iter()
+            .map(|width| self.image_size.scaled_to_width(*width))
+            .collect::<Result<Vec<ImageSize>, Error>>()?;
+        let largest = match image_sizes.iter().max_by_key(|size| size.width) {
+            Some(largest) => *largest,
+            None => return Ok(Variants::default()),
+        };
+
+        let image = self.assemble_image(largest, None)?;
+        let dimensions = image_sizes
+            .iter()
+            .map(|size| (size.width, size.height))
+            .collect::<Vec<(u32, u32)>>();
+        let variants = image::resize_variants(&image, &dimensions)
+            .iter()
+            .map(|image| -> Result<Variant, Error> {
+                Ok(Variant {
+                    width: image.width(),
+                    height: image.height(),
+                    format: self.format,
+                    base64: image::to_base_64(image, self.format)?,
+                })
+            })
+            .collect::<Result<Vec<Variant>, Error>>()?;
+        Ok(Variants::new(variants))
+    }
+
+    #[cfg(feature = "render")]
+    fn assemble_image(
+        &self,
+        image_size: ImageSize,
+        cache: Option<&PartCache>,
+    ) -> Result<RgbaImage, Error> {
         if self.is_missing_required_data() {
             return Err(Error::RoboHashMissingRequiredData);
         }
 
-        let set = files_in_set(&self.hash_array, &self.sets_root, &self.set)?;
-        let background = match &self.background_set {
-            Some(set) => background(&self.hash_array, &self.background_root, set)?,
-            None => None,
+        image_size.check()?;
+        let source = self.asset_source.as_ref();
+        let set = self.files_in_set(source)?.parts;
+        let backdrop = self.backdrop(source)?;
+        let mask = match self.mask_file()? {
+            Some(file) => {
+                materials::confine(source, self.symlinks, &self.mask_root, &file)?;
+                Some(Mask::Custom(file))
+            }
+            None => self.mask.to_owned(),
         };
 
-        let image = image::build_robo_hash_image(
+        let border = self
+            .mask_border
+            .map(|width| Border::from_hash(&self.hash_array, width))
+            .transpose()?;
+
+        let catalogue = self.catalogue.as_deref();
+        let cache = cache.map(|parts| CacheScope {
+            parts,
+            source: &self.asset_source,
+            set_fingerprint: catalogue.and_then(|catalogue| catalogue.set_fingerprint(&self.set)),
+            background_fingerprint: match &self.background {
+                Background::Set(set) => {
+                    catalogue.and_then(|catalogue| catalogue.background_fingerprint(set))
+                }
+                _ => None,
+            },
+        });
+
+        image::build_robo_hash_image(
             &set,
-            &background,
-            self.image_size.width,
-            self.image_size.height,
-        )?;
-        let base64 = image::to_base_64(&image)?;
-        Ok(base64)
+            &backdrop,
+            image_size.width,
+            image_size.height,
+            &mask,
+            &border,
+            &Loader { source, cache },
+        )
+    }
+
+    #[cfg(feature = "render")]
+    pub(crate) fn mask_file(&self) -> Result<Option<String>, Error> {
+        match &self.mask {
+            Some(Mask::Custom(mask)) => {
+                materials::check_name(mask)?;
+                let file = PathBuf::from(&self.mask_root).join(mask);
+                Ok(Some(file.to_string_lossy().into_owned()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn selector(&self) -> Selector<'_> {
+        Selector::new(self.algorithm, &self.digest, &self.hash_array).with_parts(&self.parts)
+    }
+
+    fn set_directory(&self, source: &dyn AssetSource) -> Result<PathBuf, Error> {
+        let catalogued = self
+            .catalogue
+            .as_ref()
+            .map(|catalogue| catalogue.set_fingerprint(&self.set).is_some());
+        materials::asset_directory(source, catalogued, &self.sets_root, &self.set)
+    }
+
+    fn background_directory(&self, source: &dyn AssetSource, set: &str) -> Result<PathBuf, Error> {
+        let catalogued = self
+            .catalogue
+            .as_ref()
+            .map(|catalogue| catalogue.background_fingerprint(set).is_some());
+        materials::asset_directory(source, catalogued, &self.background_root, set)
+    }
+
+    fn files_in_set(&self, source: &dyn AssetSource) -> Result<Selection, Error> {
+        self.set_directory(source)?;
+        let selector = self.selector();
+        let selection = match &self.definition {
+            Some(definition) => {
+                definition.files_in_set(source, &selector, &self.sets_root, &self.set)?
+            }
+            None => Selection {
+                parts: files_in_set(source, &selector, &self.sets_root, &self.set)?,
+                omitted: Vec::new(),
+            },
+        };
+        for part in &selection.parts {
+            materials::confine(source, self.symlinks, &self.sets_root, part)?;
+        }
+        Ok(selection)
+    }
+
+    #[cfg(feature = "contact-sheet")]
+    fn categories(&self, source: &dyn AssetSource) -> Result<Vec<String>, Error> {
+        match &self.definition {
+            Some(definition) => Ok(definition
+                .categories
+                .iter()
+                .map(|category| category.directory.to_owned())
+                .collect()),
+            None => materials::category_directories(source, &self.sets_root, &self.set),
+        }
+    }
+
+    fn backdrop(&self, source: &dyn AssetSource) -> Result<Backdrop, Error> {
+        Ok(match &self.background {
+            Background::None => Backdrop::None,
+            Background::Set(set) => {
+                self.background_directory(source, set)?;
+                let background = background(source, &self.selector(), &self.background_root, set)?;
+                materials::confine(source, self.symlinks, &self.background_root, &background)?;
+                Backdrop::Image(background)
+            }
+            Background::Solid(colour) => Backdrop::Solid(*colour),
+            Background::LinearGradient { from, to, angle } => Backdrop::LinearGradient {
+                from: *from,
+                to: *to,
+                angle: *angle,
+            },
+            Background::RadialGradient { inner, outer } => Backdrop::RadialGradient {
+                inner: *inner,
+                outer: *outer,
+            },
+            Background::HashDerived => match (self.hash_array.get(2), self.hash_array.get(3)) {
+                (Some(hue_seed), Some(offset_seed)) => Backdrop::HashDerived {
+                    hue_seed: *hue_seed,
+                    offset_seed: *offset_seed,
+                },
+                _ => {
+                    return Err(Error::InvalidArrayIndex(
+                        format!("{:?}", self.hash_array),
+                        String::from("background"),
+                    ))
+                }
+            },
+        })
     }
 
     fn is_missing_required_data(&self) -> bool {
@@ -149,83 +787,135 @@ impl RoboHash {
     }
 }
 
-fn files_in_set(hash_array: &Vec<i64>, sets_root: &str, set: &str) -> Result<Vec<String>, Error> {
-    let categories_in_set = materials::categories_in_set(sets_root, set)?;
+fn catalogue_fingerprint<S: Serializer>(
+    catalogue: &Option<Arc<Catalogue>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    catalogue
+        .as_ref()
+        .map(|catalogue| catalogue.fingerprint())
+        .serialize(serializer)
+}
+
+fn default_mask_root() -> String {
+    String::from(".")
+}
+
+fn files_in_set(
+    source: &dyn AssetSource,
+    selector: &Selector,
+    sets_root: &str,
+    set: &str,
+) -> Result<Vec<String>, Error> {
+    let categories_in_set = materials::category_directories(source, sets_root, set)?;
     let mut index = 4;
-    let mut files = categories_in_set
-        .iter()
-        .flat_map(
-            |category| match materials::files_in_category(sets_root, set, category) {
-                Ok(file) => {
-                    let set_index = (hash_array[index] % file.len() as i64) as usize;
-                    if let Some(selected_file) = file.get(set_index) {
-                        index = index + 1;
-                        Some(String::from(selected_file))
-                    } else {
-                        println!("failed to fetch index {set_index:#?} from {file:#?}");
-                        None
-                    }
-                }
-                Err(e) => {
-                    println!("{e:#?}");
-                    None
-                }
-            },
-        )
-        .collect::<Vec<String>>();
-    files.sort_by(|a, b| {
-        a.split("#").collect::<Vec<_>>()[1].cmp(b.split("#").collect::<Vec<_>>()[1])
-    });
+    let mut files = Vec::with_capacity(categories_in_set.len());
+    for category in categories_in_set.iter() {
+        let file = materials::files_in_category(source, sets_root, set, category)?;
+        if file.is_empty() {
+            return Err(Error::MalformedAssets(format!(
+                "category {set}/{category} has no parts"
+            )));
+        }
+        match selector
+            .overridden_part(category, file.len())
+            .or_else(|| selector.index(Slot::Part(index), file.len()))
+        {
+            Some(set_index) => {
+                index += 1;
+                files.push(String::from(&file[set_index]));
+            }
+            None => {
+                return Err(Error::InvalidArrayIndex(
+                    String::from("hash_array"),
+                    format!("{index}"),
+                ))
+            }
+        }
+    }
+    if let Some(file) = files.iter().find(|file| !file.contains('#')) {
+        return Err(Error::MalformedAssets(format!(
+            "{file} has no # order prefix"
+        )));
+    }
+    files.sort_by(|a, b| a.split('#').nth(1).cmp(&b.split('#').nth(1)));
     Ok(files)
 }
 
 fn background(
-    hash_array: &Vec<i64>,
+    source: &dyn AssetSource,
+    selector: &Selector,
     background_root: &str,
     set: &str,
-) -> Result<Option<String>, Error> {
-    let index = 3;
-    let backgrounds = materials::categories_in_set(background_root, set)?;
-    let set_index = (hash_array[index] % backgrounds.len() as i64) as usize;
-    Ok(match backgrounds.get(set_index) {
-        Some(background) => {
-            let background_path = [background_root, "/", set, "/", background].concat();
-            Some(background_path)
-        }
-        None => {
-            println!("failed to fetch index {set_index:#?} from {backgrounds:#?}");
-            None
+) -> Result<String, Error> {
+    let backgrounds = materials::categories_in_set(source, background_root, set)?;
+    if backgrounds.is_empty() {
+        return Err(Error::MalformedAssets(format!(
+            "background set {set} has no backgrounds"
+        )));
+    }
+    match selector.index(Slot::Background, backgrounds.len()) {
+        Some(set_index) => {
+            let background = &backgrounds[set_index];
+            Ok([background_root, "/", set, "/", background].concat())
         }
-    })
+        None => Err(Error::InvalidArrayIndex(
+            String::from("hash_array"),
+            String::from("background"),
+        )),
+    }
 }
 
 fn colour_selection(
-    hash_array: &Vec<i64>,
+    source: &dyn AssetSource,
+    selector: &Selector,
     colour: &Option<String>,
     set: &str,
     set_root: &str,
 ) -> Result<Option<String>, Error> {
     if set == SET_DEFAULT && colour.is_none() {
-        Ok(Some(random_colour(hash_array, set_root)?))
+        Ok(Some(random_colour(source, selector, set_root)?))
     } else {
         Ok(colour.clone())
     }
 }
 
-fn random_colour(hash_array: &Vec<i64>, set_root: &str) -> Result<String, Error> {
-    let available_colours = materials::categories_in_set(set_root, "set1")?;
-    let selected_index = (hash_array[0] % available_colours.len() as i64) as usize;
-    Ok(available_colours[selected_index].clone())
+fn random_colour(
+    source: &dyn AssetSource,
+    selector: &Selector,
+    set_root: &str,
+) -> Result<String, Error> {
+    let available_colours = materials::category_directories(source, set_root, SET_DEFAULT)?;
+    if available_colours.is_empty() {
+        return Err(Error::MalformedAssets(format!(
+            "{SET_DEFAULT} has no colours"
+        )));
+    }
+    match selector.index(Slot::Colour, available_colours.len()) {
+        Some(selected_index) => Ok(available_colours[selected_index].clone()),
+        None => Err(Error::InvalidArrayIndex(
+            format!("{available_colours:?}"),
+            String::from("colour"),
+        )),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs::File;
-    use std::io::Write;
+    use std::path::Path;
 
-    use crate::image::tests::load_base64_string_image_resources;
+    use std::sync::OnceLock;
 
     use super::*;
+    #[cfg(feature = "render")]
+    use crate::testing;
+
+    pub(crate) fn catalogue() -> Arc<Catalogue> {
+        static CATALOGUE: OnceLock<Arc<Catalogue>> = OnceLock::new();
+        Arc::clone(CATALOGUE.get_or_init(|| {
+            Arc::new(Catalogue::scan(&FileSystemAssetSource, "./sets", "./backgrounds").unwrap())
+        }))
+    }
 
     #[test]
     fn test_that_robo_hash_builder_returns_a_builder() {
@@ -245,7 +935,7 @@ mod tests {
         // act
         let robo_hash_builder = RoboHashBuilder::new(text);
         // assert
-        assert_eq!(robo_hash_builder.set, expected_set)
+        assert_eq!(robo_hash_builder.options.set, expected_set)
     }
 
     #[test]
@@ -256,7 +946,7 @@ mod tests {
         // act
         let robo_hash_builder = RoboHashBuilder::new(text);
         // assert
-        assert_eq!(robo_hash_builder.colour, expected_colour)
+        assert_eq!(robo_hash_builder.options.colour, expected_colour)
     }
 
     #[test]
@@ -268,7 +958,7 @@ mod tests {
         // act
         let robo_hash_builder = RoboHashBuilder::new(text).with_set(set);
         // assert
-        assert_eq!(robo_hash_builder.set, expected_set)
+        assert_eq!(robo_hash_builder.options.set, expected_set)
     }
 
     #[test]
@@ -280,7 +970,7 @@ mod tests {
         // act
         let robo_hash_builder = RoboHashBuilder::new(text).with_colour(colour);
         // assert
-        assert_eq!(robo_hash_builder.colour, expected_colour)
+        assert_eq!(robo_hash_builder.options.colour, expected_colour)
     }
 
     #[test]
@@ -292,7 +982,63 @@ mod tests {
         // act
         let robo_hash_builder = RoboHashBuilder::new(text).with_set_location(set_root);
         // assert
-        assert_eq!(robo_hash_builder.set_root, expected_set_root)
+        assert_eq!(robo_hash_builder.options.set_root, expected_set_root)
+    }
+
+    #[test]
+    fn test_that_robo_hash_builder_with_background_set_selects_a_background_set() {
+        // arrange
+        let text = "text";
+        let expected_background = Background::Set(String::from("bg1"));
+        // act
+        let robo_hash_builder = RoboHashBuilder::new(text).with_background_set("bg1");
+        // assert
+        assert_eq!(robo_hash_builder.options.background, expected_background)
+    }
+
+    #[test]
+    fn test_that_robo_hash_builder_with_background_changes_the_background() {
+        // arrange
+        let text = "text";
+        let expected_background = Background::Solid(Rgba([255, 255, 255, 255]));
+        // act
+        let robo_hash_builder = RoboHashBuilder::new(text)
+            .with_background(Background::Solid(Rgba([255, 255, 255, 255])));
+        // assert
+        assert_eq!(robo_hash_builder.options.background, expected_background)
+    }
+
+    #[test]
+    fn test_that_robo_hash_builder_with_mask_sets_the_mask() {
+        // arrange
+        let text = "text";
+        let expected_mask = Some(Mask::RoundedRectangle { radius: 16 });
+        // act
+        let robo_hash_builder =
+            RoboHashBuilder::new(text).with_mask(Mask::RoundedRectangle { radius: 16 });
+        // assert
+        assert_eq!(robo_hash_builder.options.mask, expected_mask)
+    }
+
+    #[test]
+    fn test_that_robo_hash_builder_with_mask_border_sets_the_border_width() {
+        // arrange
+        let text = "text";
+        let expected_border = Some(4);
+        // act
+        let robo_hash_builder = RoboHashBuilder::new(text).with_mask_border(4);
+        // assert
+        assert_eq!(robo_hash_builder.options.mask_border, expected_border)
+    }
+
+    #[test]
+    fn test_that_robo_hash_builder_with_algorithm_sets_the_algorithm() {
+        // arrange
+        let text = "text";
+        // act
+        let robo_hash_builder = RoboHashBuilder::new(text).with_algorithm(Algorithm::V2);
+        // assert
+        assert_eq!(robo_hash_builder.options.algorithm, Algorithm::V2)
     }
 
     #[test]
@@ -330,6 +1076,7 @@ mod tests {
         assert_eq!(robo_hash.unwrap().hash_array, expected_hash_array)
     }
 
+    #[cfg(feature = "render")]
     #[test]
     fn test_robo_hash_assemble_base64_returns_missing_data_error_when_robo_hash_does_not_contain_hash_array(
     ) {
@@ -340,11 +1087,22 @@ mod tests {
         };
         let robo_hash = RoboHash {
             image_size,
+            digest: String::new(),
             hash_array: vec![],
             set: String::from("set1"),
+            definition: None,
             sets_root: String::from("set_root"),
-            background_set: None,
+            background: Background::None,
             background_root: String::from("background_root"),
+            mask: None,
+            mask_root: String::from("."),
+            mask_border: None,
+            format: Format::Png,
+            asset_source: Arc::new(FileSystemAssetSource),
+            symlinks: SymlinkPolicy::WithinRoot,
+            catalogue: None,
+            algorithm: Algorithm::V1,
+            parts: BTreeMap::new(),
         };
         // act
         let image = robo_hash.assemble_base64();
@@ -356,6 +1114,7 @@ mod tests {
         )
     }
 
+    #[cfg(feature = "render")]
     #[test]
     fn test_robo_hash_assemble_base64_returns_missing_data_error_when_set_does_not_contain_any_data(
     ) {
@@ -366,11 +1125,22 @@ mod tests {
         };
         let robo_hash = RoboHash {
             image_size,
+            digest: String::new(),
             hash_array: vec![1, 2],
             set: String::from(""),
+            definition: None,
             sets_root: String::from("set_root"),
-            background_set: None,
+            background: Background::None,
             background_root: String::from("background_root"),
+            mask: None,
+            mask_root: String::from("."),
+            mask_border: None,
+            format: Format::Png,
+            asset_source: Arc::new(FileSystemAssetSource),
+            symlinks: SymlinkPolicy::WithinRoot,
+            catalogue: None,
+            algorithm: Algorithm::V1,
+            parts: BTreeMap::new(),
         };
         // act
         let image = robo_hash.assemble_base64();
@@ -382,6 +1152,7 @@ mod tests {
         )
     }
 
+    #[cfg(feature = "render")]
     #[test]
     fn test_robo_hash_assemble_base64_returns_missing_data_error_when_sets_root_does_not_contain_any_data(
     ) {
@@ -392,11 +1163,22 @@ mod tests {
         };
         let robo_hash = RoboHash {
             image_size,
+            digest: String::new(),
             hash_array: vec![1, 2],
             set: String::from("set1"),
+            definition: None,
             sets_root: String::from(""),
-            background_set: None,
+            background: Background::None,
             background_root: String::from("background_root"),
+            mask: None,
+            mask_root: String::from("."),
+            mask_border: None,
+            format: Format::Png,
+            asset_source: Arc::new(FileSystemAssetSource),
+            symlinks: SymlinkPolicy::WithinRoot,
+            catalogue: None,
+            algorithm: Algorithm::V1,
+            parts: BTreeMap::new(),
         };
         // act
         let image = robo_hash.assemble_base64();
@@ -408,35 +1190,610 @@ mod tests {
         )
     }
 
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_robo_hash_assemble_sizes_returns_a_variant_for_each_requested_size() {
+        // arrange
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .build()
+            .unwrap();
+        let sizes = [32, 128, 64];
+        // act
+        let variants = robo_hash.assemble_sizes(&sizes).unwrap();
+        // assert
+        let widths = variants
+            .iter()
+            .map(|variant| variant.width)
+            .collect::<Vec<u32>>();
+        assert_eq!(widths, vec![32, 128, 64]);
+        assert!(variants
+            .iter()
+            .all(|variant| variant.width == variant.height))
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_robo_hash_assemble_sizes_returns_invalid_size_error_for_zero_width() {
+        // arrange
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .build()
+            .unwrap();
+        // act
+        let variants = robo_hash.assemble_sizes(&[0]);
+        // assert
+        assert!(variants.is_err());
+        assert_eq!(
+            variants.err().unwrap().to_string(),
+            Error::InvalidImageSize(0, 0).to_string()
+        )
+    }
+
+    #[test]
+    fn test_robo_hash_cache_key_is_stable_and_depends_on_options() {
+        // arrange
+        let builder = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .with_catalogue(catalogue());
+        // act
+        let first = builder.build().unwrap().cache_key().unwrap();
+        let second = builder.build().unwrap().cache_key().unwrap();
+        let resized = builder
+            .clone()
+            .with_size(64, 64)
+            .build()
+            .unwrap()
+            .cache_key()
+            .unwrap();
+        let other_text = RoboHashBuilder::new("other")
+            .with_options(builder.options.clone())
+            .build()
+            .unwrap()
+            .cache_key()
+            .unwrap();
+        let uncatalogued = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .build()
+            .unwrap()
+            .cache_key();
+        // assert
+        assert!(matches!(uncatalogued, Err(Error::CatalogueRequired(_))));
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 32);
+        assert_ne!(first, resized);
+        assert_ne!(first, other_text)
+    }
+
+    #[test]
+    fn test_robo_hash_manifest_lists_selected_parts_and_fingerprints() {
+        // arrange
+        let catalogue = catalogue();
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .with_background_set("bg1")
+            .build()
+            .unwrap();
+        // act
+        let manifest = robo_hash.manifest().unwrap();
+        // assert
+        assert_eq!(manifest.parts.len(), 5);
+        assert!(manifest.background.is_some());
+        assert_eq!(
+            Some(manifest.set_fingerprint.as_str()),
+            catalogue.set_fingerprint("set4")
+        );
+        assert_eq!(
+            manifest.background_fingerprint.as_deref(),
+            catalogue.background_fingerprint("bg1")
+        )
+    }
+
+    #[test]
+    fn test_robo_hash_v2_selects_a_part_from_every_category() {
+        // arrange
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .with_algorithm(Algorithm::V2)
+            .build()
+            .unwrap();
+        // act
+        let manifest = robo_hash.manifest().unwrap();
+        // assert
+        assert_eq!(manifest.parts.len(), 5)
+    }
+
+    #[test]
+    fn test_robo_hash_resolve_selects_parts_and_background_without_decoding_images() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("./sets/custom/000#a/000#a0.png"), vec![]);
+        assets.insert_file(Path::new("./sets/custom/001#b/000#b0.png"), vec![]);
+        assets.insert_file(Path::new("./backgrounds/bg/000#bg0.png"), vec![]);
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("custom")
+            .with_background_set("bg")
+            .with_asset_source(assets)
+            .build()
+            .unwrap();
+        // act
+        let resolution = robo_hash.resolve();
+        // assert
+        assert_eq!(
+            resolution.unwrap(),
+            Resolution {
+                set: String::from("custom"),
+                parts: vec![
+                    String::from("./sets/custom/000#a/000#a0.png"),
+                    String::from("./sets/custom/001#b/000#b0.png")
+                ],
+                omitted_categories: vec![],
+                background: Some(String::from("./backgrounds/bg/000#bg0.png")),
+            }
+        )
+    }
+
+    #[test]
+    fn test_robo_hash_rejects_names_outside_of_the_asset_roots() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("./sets/custom/000#a/000#a0.png"), vec![]);
+        assets.insert_file(Path::new("./sets/set1/red/000#a/000#a0.png"), vec![]);
+        assets.insert_file(Path::new("./secret/000#a/000#a0.png"), vec![]);
+        let builder = RoboHashBuilder::new("test").with_asset_source(assets);
+        // act
+        let set = builder.clone().with_set("../secret").build();
+        let colour = builder
+            .clone()
+            .with_set("set1")
+            .with_colour("../../secret")
+            .build();
+        let background = builder
+            .clone()
+            .with_set("custom")
+            .with_background_set("../sets/custom")
+            .build()
+            .unwrap()
+            .resolve();
+        let unknown = builder.clone().with_set("missing").build();
+        // assert
+        assert!(matches!(set, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(colour, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(background, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(unknown, Err(Error::UnknownAsset(_))))
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_robo_hash_rejects_custom_masks_outside_of_the_mask_root() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("./sets/custom/000#a/000#a0.png"), vec![]);
+        assets.insert_file(Path::new("./secret.png"), vec![]);
+        let builder = RoboHashBuilder::new("test")
+            .with_set("custom")
+            .with_size(8, 8)
+            .with_mask_location("./masks")
+            .with_asset_source(assets);
+        let mut escaped = builder.build().unwrap();
+        escaped.mask = Some(Mask::Custom(String::from("../secret.png")));
+        // act
+        let parent = builder
+            .clone()
+            .with_mask(Mask::Custom(String::from("../secret.png")))
+            .build();
+        let absolute = builder
+            .clone()
+            .with_mask(Mask::Custom(String::from("/secret.png")))
+            .build();
+        let assembled = escaped.assemble();
+        // assert
+        assert!(matches!(parent, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(absolute, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(assembled, Err(Error::AssetOutsideRoot(_))))
+    }
+
+    #[test]
+    fn test_robo_hash_returns_errors_for_malformed_sets() {
+        // arrange
+        let set = |files: &[&str]| {
+            let mut assets = MemoryAssetSource::new();
+            assets.insert_directory(Path::new("./sets/set1"), Vec::new());
+            for file in files {
+                match file.strip_suffix('/') {
+                    Some(directory) => assets.insert_directory(Path::new(directory), Vec::new()),
+                    None => assets.insert_file(Path::new(file), vec![]),
+                }
+            }
+            RoboHashBuilder::new("test")
+                .with_set("custom")
+                .with_asset_source(assets)
+        };
+        let categories = (0..20)
+            .map(|category| format!("./sets/custom/{category:03}#a/000#a.png"))
+            .collect::<Vec<String>>();
+        let categories = categories.iter().map(String::as_str).collect::<Vec<&str>>();
+        // act
+        let empty_category = set(&["./sets/custom/000#a/"]).build().unwrap().resolve();
+        let unordered = set(&["./sets/custom/a/a.png"]).build().unwrap().resolve();
+        let too_many = set(&categories).build().unwrap().resolve();
+        let no_colours = set(&[]).with_set("set1").build();
+        let negative = set(&["./sets/custom/000#a/000#a.png"])
+            .with_hash_array(&[-7; 11])
+            .build()
+            .unwrap()
+            .resolve();
+        let short_hash = set(&["./sets/custom/000#a/000#a.png"])
+            .with_hash_array(&[1])
+            .with_background(Background::HashDerived)
+            .with_mask_border(2)
+            .build()
+            .unwrap();
+        // assert
+        assert!(matches!(empty_category, Err(Error::MalformedAssets(_))));
+        assert!(matches!(unordered, Err(Error::MalformedAssets(_))));
+        assert!(matches!(too_many, Err(Error::InvalidArrayIndex(_, _))));
+        assert!(matches!(no_colours, Err(Error::MalformedAssets(_))));
+        assert_eq!(negative.unwrap().parts.len(), 1);
+        assert!(matches!(
+            short_hash.resolve(),
+            Err(Error::InvalidArrayIndex(_, _))
+        ))
+    }
+
+    #[test]
+    fn test_robo_hash_returns_an_error_for_unreadable_categories() {
+        // arrange
+        #[derive(Debug)]
+        struct Unreadable(MemoryAssetSource);
+        impl AssetSource for Unreadable {
+            fn list(&self, directory: &Path) -> Result<Vec<String>, Error> {
+                match directory.ends_with("001#b") {
+                    true => Err(Error::IoError(std::io::ErrorKind::PermissionDenied.into())),
+                    false => self.0.list(directory),
+                }
+            }
+            fn read(&self, file: &Path) -> Result<Vec<u8>, Error> {
+                self.0.read(file)
+            }
+            fn is_dir(&self, path: &Path) -> bool {
+                path.ends_with("001#b") || self.0.is_dir(path)
+            }
+        }
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("./sets/custom/000#a/000#a.png"), vec![]);
+        assets.insert_file(Path::new("./sets/custom/001#b/000#b.png"), vec![]);
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("custom")
+            .with_asset_source(Unreadable(assets))
+            .build()
+            .unwrap();
+        // act
+        let resolution = robo_hash.resolve();
+        // assert
+        assert!(matches!(resolution, Err(Error::IoError(_))))
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_robo_hash_assemble_returns_invalid_size_error_for_unrepresentable_sizes() {
+        // arrange
+        let builder = RoboHashBuilder::new("test").with_set("set4");
+        // act
+        let zero = builder.clone().with_size(0, 16).build().unwrap().assemble();
+        let huge = builder
+            .clone()
+            .with_size(u32::MAX, u32::MAX)
+            .build()
+            .unwrap()
+            .assemble();
+        let too_large = builder.with_size(65536, 65536).build().unwrap().assemble();
+        // assert
+        assert!(matches!(zero, Err(Error::InvalidImageSize(0, 16))));
+        assert!(matches!(huge, Err(Error::InvalidImageSize(_, _))));
+        assert!(matches!(
+            too_large,
+            Err(Error::InvalidImageSize(65536, 65536))
+        ))
+    }
+
+    #[test]
+    fn test_robo_hash_with_seed_is_deterministic_per_seed() {
+        // arrange
+        let cache_key = |text: &str, seed: u64| {
+            let robo_hash = RoboHashBuilder::new(text)
+                .with_set("set4")
+                .with_catalogue(catalogue())
+                .with_seed(seed)
+                .build()
+                .unwrap();
+            robo_hash.cache_key().unwrap()
+        };
+        // act
+        let first = cache_key("first", 1);
+        let again = cache_key("second", 1);
+        let other = cache_key("first", 2);
+        // assert
+        assert_eq!(first, again);
+        assert_ne!(first, other)
+    }
+
+    #[test]
+    fn test_robo_hash_with_hash_array_selects_parts_by_index() {
+        // arrange
+        let hash_array = [0_i64; 22];
+        // act
+        let robo_hash = RoboHashBuilder::new("ignored")
+            .with_set("set4")
+            .with_hash_array(&hash_array)
+            .build()
+            .unwrap();
+        // assert
+        let manifest = robo_hash.manifest().unwrap();
+        assert_eq!(manifest.parts[0], "./sets/set4/000#00body/000#body0.png");
+        assert_eq!(manifest.parts.len(), 5)
+    }
+
+    #[test]
+    fn test_robo_hash_with_parts_overrides_only_the_chosen_category() {
+        // arrange
+        let builder = RoboHashBuilder::new("test").with_set("set4");
+        let original = builder.build().unwrap().manifest().unwrap();
+        // act
+        let rerolled = builder
+            .with_parts(&[("00body", 2)])
+            .build()
+            .unwrap()
+            .manifest()
+            .unwrap();
+        // assert
+        assert_eq!(rerolled.parts[0], "./sets/set4/000#00body/002#body2.png");
+        assert_eq!(rerolled.parts[1..], original.parts[1..])
+    }
+
+    #[test]
+    fn test_that_robo_hash_builder_build_uses_set_definition_when_present() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(
+            Path::new("./sets/custom/set.toml"),
+            br#"
+                colours = ["red"]
+                default_size = { width = 48, height = 32 }
+
+                [[categories]]
+                directory = "eyes"
+                z_index = 1
+
+                [[categories]]
+                directory = "body"
+                z_index = 0
+            "#
+            .to_vec(),
+        );
+        assets.insert_file(Path::new("./sets/custom/red/eyes/eyes.png"), vec![]);
+        assets.insert_file(Path::new("./sets/custom/red/body/body.png"), vec![]);
+        // act
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("custom")
+            .with_asset_source(assets)
+            .build()
+            .unwrap();
+        // assert
+        let manifest = robo_hash.manifest().unwrap();
+        assert_eq!(manifest.set, "custom/red");
+        assert_eq!(
+            manifest.parts,
+            vec![
+                "./sets/custom/red/body/body.png",
+                "./sets/custom/red/eyes/eyes.png"
+            ]
+        );
+        assert_eq!(
+            (robo_hash.image_size.width, robo_hash.image_size.height),
+            (48, 32)
+        )
+    }
+
+    #[cfg(feature = "render")]
+    const GOLDEN_CASES: [(&str, &str, Option<&str>); 5] = [
+        ("test", "set1", Some("bg1")),
+        ("robohash", "set2", None),
+        ("avatar", "set3", Some("bg2")),
+        ("user@example.com", "set4", None),
+        ("42", "set5", Some("bg1")),
+    ];
+
+    #[cfg(feature = "render")]
+    fn golden_image(algorithm: Algorithm, case: (&str, &str, Option<&str>)) -> Vec<u8> {
+        let (text, set, background_set) = case;
+        let mut builder = RoboHashBuilder::new(text)
+            .with_set(set)
+            .with_size(128, 128)
+            .with_algorithm(algorithm);
+        if let Some(background_set) = background_set {
+            builder = builder.with_background_set(background_set);
+        }
+        builder.build().unwrap().assemble().unwrap()
+    }
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_builder_and_robo_hash_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<RoboHashBuilder>();
+        assert_send_sync::<RoboHash>();
+        assert_send_sync::<RenderOptions>();
+    }
+
+    #[test]
+    fn test_builder_round_trips_through_serde() {
+        // arrange
+        let builder = RoboHashBuilder::new("test")
+            .with_set("set2")
+            .with_background(Background::LinearGradient {
+                from: Rgba([0, 0, 0, 255]),
+                to: Rgba([255, 255, 255, 255]),
+                angle: 45.0,
+            })
+            .with_mask(Mask::Circle)
+            .with_format(Format::Jpeg { quality: 80 })
+            .with_algorithm(Algorithm::V2)
+            .with_parts(&[("Eyes", 2)]);
+        // act
+        let json = serde_json::to_string(&builder).unwrap();
+        let replayed = serde_json::from_str::<RoboHashBuilder>(&json).unwrap();
+        // assert
+        assert_eq!(replayed, builder);
+        assert_eq!(hash_of(&replayed), hash_of(&builder));
+        assert_eq!(replayed.build().unwrap(), builder.build().unwrap())
+    }
+
+    #[test]
+    fn test_robo_hash_round_trips_through_serde_and_resolves_the_same_parts() {
+        // arrange
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set3")
+            .with_background_set("bg2")
+            .build()
+            .unwrap();
+        // act
+        let json = serde_json::to_string(&robo_hash).unwrap();
+        let replayed = serde_json::from_str::<RoboHash>(&json).unwrap();
+        // assert
+        assert_eq!(replayed, robo_hash);
+        assert_eq!(hash_of(&replayed), hash_of(&robo_hash));
+        assert_eq!(replayed.resolve().unwrap(), robo_hash.resolve().unwrap())
+    }
+
+    #[test]
+    fn test_equality_includes_the_asset_source() {
+        // arrange
+        let builder = RoboHashBuilder::new("test");
+        let robo_hash = builder.build().unwrap();
+        let with_memory_source = builder.clone().with_asset_source(MemoryAssetSource::new());
+        // act
+        let replayed = robo_hash
+            .clone()
+            .with_asset_source(MemoryAssetSource::new());
+        let cloned = with_memory_source.clone();
+        // assert
+        assert_ne!(with_memory_source, builder);
+        assert_eq!(cloned, with_memory_source);
+        assert_eq!(hash_of(&cloned), hash_of(&with_memory_source));
+        assert_eq!(RoboHashBuilder::new("test"), builder);
+        assert_ne!(replayed, robo_hash)
+    }
+
+    #[test]
+    fn test_builder_and_robo_hash_work_as_map_keys() {
+        // arrange
+        let gradient = |angle| {
+            RoboHashBuilder::new("test").with_background(Background::LinearGradient {
+                from: Rgba([0, 0, 0, 255]),
+                to: Rgba([255, 255, 255, 255]),
+                angle,
+            })
+        };
+        let mut builders = std::collections::HashMap::new();
+        let mut robo_hashes = std::collections::HashSet::new();
+        // act
+        builders.insert(gradient(0.0), "zero");
+        builders.insert(gradient(-0.0), "negative zero");
+        builders.insert(gradient(45.0), "diagonal");
+        robo_hashes.insert(gradient(45.0).build().unwrap());
+        robo_hashes.insert(gradient(45.0).build().unwrap());
+        // assert
+        assert_eq!(builders.len(), 2);
+        assert_eq!(builders[&gradient(0.0)], "negative zero");
+        assert_eq!(robo_hashes.len(), 1)
+    }
+
+    #[test]
+    fn test_robo_hash_serializes_the_catalogue_fingerprint() {
+        // arrange
+        let catalogue =
+            Arc::new(Catalogue::scan(&FileSystemAssetSource, "./sets", "./backgrounds").unwrap());
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_catalogue(Arc::clone(&catalogue))
+            .build()
+            .unwrap();
+        // act
+        let json = serde_json::to_value(&robo_hash).unwrap();
+        let replayed = serde_json::from_value::<RoboHash>(json.clone()).unwrap();
+        // assert
+        assert_eq!(json["catalogue"], catalogue.fingerprint());
+        assert_eq!(replayed.catalogue, None);
+        assert_eq!(replayed.resolve().unwrap(), robo_hash.resolve().unwrap())
+    }
+
+    #[test]
+    fn test_keyed_builder_fails_to_serialize_with_a_clear_error() {
+        // arrange
+        let builder = RoboHashBuilder::new("test").with_key(b"secret");
+        // act
+        let serialized = serde_json::to_string(&builder);
+        // assert
+        let error = serialized.unwrap_err().to_string();
+        assert!(error.contains("with a key can't be serialized"))
+    }
+
+    #[cfg(feature = "render")]
+    fn golden_image_path(algorithm: Algorithm, case: (&str, &str, Option<&str>)) -> String {
+        let (text, set, background_set) = case;
+        format!(
+            "./test_resources/golden/{algorithm:?}/{text}_{set}_{}.png",
+            background_set.unwrap_or("none")
+        )
+        .to_lowercase()
+    }
+
+    #[cfg(feature = "render")]
+    fn assert_golden_images_match(algorithm: Algorithm) {
+        for case in GOLDEN_CASES {
+            let actual = golden_image(algorithm, case);
+            testing::assert_golden_image(&actual, golden_image_path(algorithm, case), 2)
+        }
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_robo_hash_v1_output_matches_golden_images() {
+        assert_golden_images_match(Algorithm::V1)
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_robo_hash_v2_output_matches_golden_images() {
+        assert_golden_images_match(Algorithm::V2)
+    }
+
+    #[cfg(feature = "render")]
     #[test]
-    #[ignore]
     fn test_that_robo_hash_image_is_generated() {
         // arrange
         let initial_string = "test";
         let set = SET_DEFAULT;
-        let colour: Option<String> = None;
         let background_set = "bg1";
 
-        let set = set.clone();
-        let test_resource = format!("{initial_string:#?}_{set:#?}_{colour:#?}_{background_set:#?}");
-        let expected_robo_hash = load_base64_string_image_resources(&test_resource);
-
         // act
         let robo_hash = RoboHashBuilder::new(initial_string)
-            .with_set(set.clone())
+            .with_set(set)
             .with_background_set(background_set)
             .build()
             .unwrap();
-        let constructed_robo_hash = robo_hash.assemble_base64().unwrap();
+        let constructed_robo_hash = robo_hash.assemble().unwrap();
 
         // assert
-        assert_eq!(constructed_robo_hash, expected_robo_hash);
-    }
-
-    fn _write_to_test_resources(location: &str, content: &str) -> std::io::Result<()> {
-        let file_location = format!("./test_resources/{}.txt", location);
-        let mut file = File::create(file_location)?;
-        file.write_all(content.as_bytes())?;
-        Ok(())
+        testing::assert_golden_image(
+            &constructed_robo_hash,
+            "./test_resources/test_set1_bg1.png",
+            2,
+        )
     }
 }
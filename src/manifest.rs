@@ -0,0 +1,20 @@
+use crate::Algorithm;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub set: String,
+    pub algorithm: Algorithm,
+    pub parts: Vec<String>,
+    pub omitted_categories: Vec<String>,
+    pub background: Option<String>,
+    pub set_fingerprint: String,
+    pub background_fingerprint: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Resolution {
+    pub set: String,
+    pub parts: Vec<String>,
+    pub omitted_categories: Vec<String>,
+    pub background: Option<String>,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Mask {
+    Circle,
+    RoundedRectangle { radius: u32 },
+    Squircle,
+    Hexagon,
+    Custom(String),
+}
@@ -0,0 +1,261 @@
+use std::f32::consts::SQRT_2;
+
+use image::{imageops, DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::asset::AssetSource;
+use crate::error::Error;
+use crate::mask::Mask;
+use crate::{image as robo_image, palette};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Border {
+    pub(crate) width: u32,
+    pub(crate) colour: Rgba<u8>,
+}
+
+impl Border {
+    pub(crate) fn from_hash(hash_array: &[i64], width: u32) -> Result<Self, Error> {
+        let seed = match hash_array.get(1) {
+            Some(seed) => *seed,
+            None => {
+                return Err(Error::InvalidArrayIndex(
+                    format!("{hash_array:?}"),
+                    String::from("border"),
+                ))
+            }
+        };
+        let colour = palette::hash_colour(seed, 0.55, 0.45);
+        Ok(Self { width, colour })
+    }
+}
+
+pub(crate) fn apply_mask(
+    image: &mut RgbaImage,
+    mask: &Mask,
+    border: &Option<Border>,
+    source: &dyn AssetSource,
+) -> Result<(), Error> {
+    match mask {
+        Mask::Custom(mask_path) => apply_custom_mask(image, mask_path, border, source),
+        shape => {
+            apply_shape_mask(image, shape, border);
+            Ok(())
+        }
+    }
+}
+
+fn apply_shape_mask(image: &mut RgbaImage, mask: &Mask, border: &Option<Border>) {
+    let (width, height) = image.dimensions();
+    let half_width = width as f32 / 2.0;
+    let half_height = height as f32 / 2.0;
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let px = x as f32 + 0.5 - half_width;
+        let py = y as f32 + 0.5 - half_height;
+        let distance = signed_distance(mask, px, py, half_width, half_height);
+        let coverage = (0.5 - distance).clamp(0.0, 1.0);
+        if let Some(border) = border {
+            let inner = (0.5 + distance + border.width as f32).clamp(0.0, 1.0);
+            palette::blend_over(pixel, border.colour, coverage * inner);
+        }
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+}
+
+fn apply_custom_mask(
+    image: &mut RgbaImage,
+    mask_path: &str,
+    border: &Option<Border>,
+    source: &dyn AssetSource,
+) -> Result<(), Error> {
+    let (width, height) = image.dimensions();
+    let mask = robo_image::try_open_image(source, mask_path)?;
+    let coverage = coverage_of(&mask);
+    let coverage = imageops::resize(&coverage, width, height, imageops::FilterType::Triangle);
+    let depths = border.map(|_| edge_depths(&coverage));
+    for (index, (pixel, coverage)) in image.pixels_mut().zip(coverage.pixels()).enumerate() {
+        if let (Some(border), Some(depths)) = (border, &depths) {
+            let inner = (border.width as f32 + 1.0 - depths[index]).clamp(0.0, 1.0);
+            palette::blend_over(pixel, border.colour, coverage[0] as f32 / 255.0 * inner);
+        }
+        pixel[3] = (pixel[3] as u32 * coverage[0] as u32 / 255) as u8;
+    }
+    Ok(())
+}
+
+fn edge_depths(coverage: &image::GrayImage) -> Vec<f32> {
+    let (width, height) = (coverage.width() as usize, coverage.height() as usize);
+    let mut depths = coverage
+        .enumerate_pixels()
+        .map(|(x, y, coverage)| match coverage[0] < 128 {
+            true => 0.0,
+            false => (x + 1)
+                .min(y + 1)
+                .min(width as u32 - x)
+                .min(height as u32 - y) as f32,
+        })
+        .collect::<Vec<f32>>();
+    let forward = [
+        (-1, 0, 1.0),
+        (-1, -1, SQRT_2),
+        (0, -1, 1.0),
+        (1, -1, SQRT_2),
+    ];
+    let backward = forward.map(|(dx, dy, cost)| (-dx, -dy, cost));
+    let mut relax = |x: usize, y: usize, neighbours: &[(isize, isize, f32)]| {
+        for (dx, dy, cost) in neighbours {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                continue;
+            }
+            let neighbour = depths[ny as usize * width + nx as usize] + cost;
+            let depth = &mut depths[y * width + x];
+            *depth = depth.min(neighbour);
+        }
+    };
+    for y in 0..height {
+        for x in 0..width {
+            relax(x, y, &forward);
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            relax(x, y, &backward);
+        }
+    }
+    depths
+}
+
+fn coverage_of(mask: &DynamicImage) -> image::GrayImage {
+    if mask.color().has_alpha() {
+        let (width, height) = mask.dimensions();
+        let rgba = mask.to_rgba8();
+        image::GrayImage::from_fn(width, height, |x, y| image::Luma([rgba.get_pixel(x, y)[3]]))
+    } else {
+        mask.to_luma8()
+    }
+}
+
+fn signed_distance(mask: &Mask, x: f32, y: f32, half_width: f32, half_height: f32) -> f32 {
+    let half_size = half_width.min(half_height);
+    match mask {
+        Mask::Circle => (x * x + y * y).sqrt() - half_size,
+        Mask::RoundedRectangle { radius } => {
+            let radius = (*radius as f32).min(half_size);
+            let qx = x.abs() - half_width + radius;
+            let qy = y.abs() - half_height + radius;
+            let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt();
+            outside + qx.max(qy).min(0.0) - radius
+        }
+        Mask::Squircle => {
+            let u = x / half_width;
+            let v = y / half_height;
+            ((u.powi(4) + v.powi(4)).powf(0.25) - 1.0) * half_size
+        }
+        Mask::Hexagon => {
+            let (kx, ky, kz) = (-0.866_025_4_f32, 0.5_f32, 0.577_350_3_f32);
+            let inradius = half_size * 0.866_025_4;
+            let (mut px, mut py) = (x.abs(), y.abs());
+            let fold = 2.0 * (kx * px + ky * py).min(0.0);
+            px -= fold * kx;
+            py -= fold * ky;
+            px -= px.clamp(-kz * inradius, kz * inradius);
+            py -= inradius;
+            (px * px + py * py).sqrt() * py.signum()
+        }
+        Mask::Custom(_) => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::FileSystemAssetSource;
+
+    fn opaque_image(size: u32) -> RgbaImage {
+        RgbaImage::from_pixel(size, size, Rgba([200, 200, 200, 255]))
+    }
+
+    #[test]
+    fn apply_mask_circle_clears_corners_and_keeps_centre() {
+        // arrange
+        let mut image = opaque_image(64);
+        // act
+        let masked = apply_mask(&mut image, &Mask::Circle, &None, &FileSystemAssetSource);
+        // assert
+        assert!(masked.is_ok());
+        assert_eq!(image.get_pixel(0, 0)[3], 0);
+        assert_eq!(image.get_pixel(63, 63)[3], 0);
+        assert_eq!(image.get_pixel(32, 32)[3], 255)
+    }
+
+    #[test]
+    fn apply_mask_antialiases_the_edge() {
+        // arrange
+        let mut image = opaque_image(64);
+        // act
+        apply_mask(&mut image, &Mask::Circle, &None, &FileSystemAssetSource).unwrap();
+        // assert
+        let partially_covered = image.pixels().filter(|p| p[3] > 0 && p[3] < 255).count();
+        assert!(partially_covered > 0)
+    }
+
+    #[test]
+    fn apply_mask_with_border_draws_the_border_colour_inside_the_edge() {
+        // arrange
+        let mut image = opaque_image(64);
+        let colour = Rgba([255, 0, 0, 255]);
+        let border = Some(Border { width: 4, colour });
+        // act
+        apply_mask(
+            &mut image,
+            &Mask::RoundedRectangle { radius: 8 },
+            &border,
+            &FileSystemAssetSource,
+        )
+        .unwrap();
+        // assert
+        assert_eq!(*image.get_pixel(32, 1), colour);
+        assert_eq!(*image.get_pixel(32, 32), Rgba([200, 200, 200, 255]))
+    }
+
+    #[test]
+    fn apply_mask_custom_with_border_draws_the_border_inside_the_alpha_edge() {
+        // arrange
+        let mut image = opaque_image(32);
+        let colour = Rgba([255, 0, 0, 255]);
+        let border = Some(Border { width: 2, colour });
+        let mask = RgbaImage::from_fn(32, 32, |x, _| match x < 16 {
+            true => Rgba([0, 0, 0, 255]),
+            false => Rgba([0, 0, 0, 0]),
+        });
+        let mut assets = crate::asset::MemoryAssetSource::new();
+        assets.insert_file(
+            std::path::Path::new("mask.png"),
+            robo_image::encode(&mask, crate::Format::Png).unwrap(),
+        );
+        // act
+        apply_mask(
+            &mut image,
+            &Mask::Custom(String::from("mask.png")),
+            &border,
+            &assets,
+        )
+        .unwrap();
+        // assert
+        assert_eq!(*image.get_pixel(14, 16), colour);
+        assert_eq!(*image.get_pixel(0, 16), colour);
+        assert_eq!(*image.get_pixel(8, 16), Rgba([200, 200, 200, 255]));
+        assert_eq!(image.get_pixel(24, 16)[3], 0)
+    }
+
+    #[test]
+    fn apply_mask_custom_returns_error_when_mask_cannot_be_opened() {
+        // arrange
+        let mut image = opaque_image(8);
+        let mask = Mask::Custom(String::from("./does/not/exist.png"));
+        // act
+        let masked = apply_mask(&mut image, &mask, &None, &FileSystemAssetSource);
+        // assert
+        assert!(masked.is_err())
+    }
+}
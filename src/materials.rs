@@ -1,52 +1,177 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
+use crate::asset::{AssetSource, SymlinkPolicy};
 use crate::error::Error;
 
-pub(crate) fn categories_in_set(root: &str, set: &str) -> Result<Vec<String>, Error> {
+pub(crate) fn categories_in_set(
+    source: &dyn AssetSource,
+    root: &str,
+    set: &str,
+) -> Result<Vec<String>, Error> {
+    check_name(set)?;
     let sets_dir = Path::new(root).join(set);
-    let sets = directories_in_path(&sets_dir)?;
+    let sets = source.list(&sets_dir)?;
     Ok(sets)
 }
 
+pub(crate) fn category_directories(
+    source: &dyn AssetSource,
+    root: &str,
+    set: &str,
+) -> Result<Vec<String>, Error> {
+    let categories = categories_in_set(source, root, set)?
+        .into_iter()
+        .filter(|category| source.is_dir(&Path::new(root).join(set).join(category)))
+        .collect();
+    Ok(categories)
+}
+
 pub(crate) fn files_in_category(
+    source: &dyn AssetSource,
     root: &str,
     set: &str,
     category: &str,
 ) -> Result<Vec<String>, Error> {
+    check_name(set)?;
+    check_name(category)?;
     let directory = path_builder(root, set, category);
-    let files = directories_in_path(&directory)?
+    let files = source
+        .list(&directory)?
         .iter()
-        .flat_map(|dir| {
-            if let Some(path) = directory.join(dir).as_path().to_str() {
-                Some(String::from(path))
-            } else {
-                println!("cannot create directory {directory:#?}/{dir:#?}");
-                None
-            }
-        })
+        .filter_map(|file| directory.join(file).to_str().map(String::from))
         .collect::<Vec<String>>();
     Ok(files)
 }
 
+pub(crate) fn asset_directory(
+    source: &dyn AssetSource,
+    catalogued: Option<bool>,
+    root: &str,
+    name: &str,
+) -> Result<PathBuf, Error> {
+    check_name(name)?;
+    if catalogued == Some(false) {
+        return Err(Error::UnknownAsset(String::from(name)));
+    }
+    let mut directory = PathBuf::from(root);
+    for component in name.split('/') {
+        if catalogued.is_none() && !source.list(&directory)?.iter().any(|e| e == component) {
+            return Err(Error::UnknownAsset(String::from(name)));
+        }
+        directory.push(component);
+    }
+    Ok(directory)
+}
+
+pub(crate) trait Links {
+    fn is_symlink(&self, path: &Path) -> bool;
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error>;
+}
+
+impl<S: AssetSource + ?Sized> Links for S {
+    fn is_symlink(&self, path: &Path) -> bool {
+        AssetSource::is_symlink(self, path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        AssetSource::canonicalize(self, path)
+    }
+}
+
+pub(crate) fn confine<L: Links + ?Sized>(
+    links: &L,
+    policy: SymlinkPolicy,
+    root: &str,
+    file: &str,
+) -> Result<(), Error> {
+    let root = Path::new(root);
+    let path = Path::new(file);
+    let confined = match policy {
+        SymlinkPolicy::Follow => true,
+        SymlinkPolicy::Deny => !below_root(root, path).any(|ancestor| links.is_symlink(ancestor)),
+        SymlinkPolicy::WithinRoot => links
+            .canonicalize(path)?
+            .starts_with(links.canonicalize(root)?),
+    };
+    if !confined {
+        return Err(Error::AssetOutsideRoot(String::from(file)));
+    }
+    Ok(())
+}
+
+pub(crate) fn below_root<'a>(root: &'a Path, path: &'a Path) -> impl Iterator<Item = &'a Path> {
+    path.ancestors()
+        .take_while(move |ancestor| *ancestor != root)
+}
+
+pub(crate) fn check_name(name: &str) -> Result<(), Error> {
+    let plain = name.split('/').all(|component| {
+        let mut components = Path::new(component).components();
+        matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+    });
+    if !plain {
+        return Err(Error::AssetOutsideRoot(String::from(name)));
+    }
+    Ok(())
+}
+
 fn path_builder(sets_root: &str, set: &str, category: &str) -> PathBuf {
     Path::new(sets_root).join(set).join(category)
 }
 
-fn directories_in_path(path: &PathBuf) -> Result<Vec<String>, Error> {
-    let mut directories = path
-        .read_dir()?
-        .into_iter()
-        .filter_map(|path| match path {
-            Ok(path) => match path.file_name().into_string() {
-                Ok(set) => Some(set),
-                Err(e) => {
-                    println!("{e:#?}");
-                    None
-                }
-            },
-            Err(_) => None,
-        })
-        .collect::<Vec<String>>();
-    directories.sort();
-    Ok(directories)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::{FileSystemAssetSource, MemoryAssetSource};
+
+    #[test]
+    fn asset_directory_accepts_listed_names_and_rejects_traversal() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("sets/set1/red/000#a/000#a.png"), vec![]);
+        // act
+        let coloured = asset_directory(&assets, None, "sets", "set1/red");
+        let unknown = asset_directory(&assets, None, "sets", "set1/blue");
+        let catalogued = asset_directory(&assets, Some(false), "sets", "set1");
+        let traversals = ["..", "../etc", "set1/../../etc", "/etc", "set1//red", ""]
+            .map(|name| asset_directory(&assets, None, "sets", name));
+        // assert
+        assert_eq!(coloured.unwrap(), Path::new("sets/set1/red"));
+        assert!(matches!(unknown, Err(Error::UnknownAsset(_))));
+        assert!(matches!(catalogued, Err(Error::UnknownAsset(_))));
+        assert!(traversals
+            .iter()
+            .all(|traversal| matches!(traversal, Err(Error::AssetOutsideRoot(_)))))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn confine_applies_the_symlink_policy() {
+        // arrange
+        let directory = std::env::temp_dir().join(format!("robohash-{}", std::process::id()));
+        let root = directory.join("sets");
+        std::fs::create_dir_all(root.join("set")).unwrap();
+        std::fs::write(directory.join("secret.png"), [0]).unwrap();
+        std::fs::write(root.join("set/inside.png"), [0]).unwrap();
+        std::os::unix::fs::symlink(directory.join("secret.png"), root.join("set/outside.png"))
+            .unwrap();
+        std::os::unix::fs::symlink(root.join("set/inside.png"), root.join("set/link.png")).unwrap();
+        let root = root.to_str().unwrap();
+        let file = |name: &str| format!("{root}/set/{name}");
+        let confine =
+            |policy, name: &str| confine(&FileSystemAssetSource, policy, root, &file(name)).is_ok();
+        // act
+        let within_root = ["inside.png", "link.png", "outside.png"]
+            .map(|name| confine(SymlinkPolicy::WithinRoot, name));
+        let deny = ["inside.png", "link.png"].map(|name| confine(SymlinkPolicy::Deny, name));
+        let follow = confine(SymlinkPolicy::Follow, "outside.png");
+        std::fs::remove_dir_all(&directory).unwrap();
+        // assert
+        assert_eq!(within_root, [true, true, false]);
+        assert_eq!(deny, [true, false]);
+        assert!(follow)
+    }
 }
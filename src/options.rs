@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use serde::ser::Error as _;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::asset::{self, AssetSource};
+use crate::{
+    Algorithm, Background, Catalogue, Format, ImageSize, Mask, SymlinkPolicy, SET_DEFAULT,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) enum HashInput {
+    #[default]
+    Text,
+    Seed(u64),
+    HashArray(Vec<i64>),
+    #[serde(skip_deserializing, serialize_with = "reject_key")]
+    Keyed(Key),
+}
+
+fn reject_key<S: Serializer>(_: &Key, _: S) -> Result<S::Ok, S::Error> {
+    Err(S::Error::custom(
+        "options with a key can't be serialized, the key has to be set again with `with_key`",
+    ))
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Key(pub(crate) Vec<u8>);
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderOptions {
+    pub(crate) colour: Option<String>,
+    pub(crate) image_size: Option<ImageSize>,
+    pub(crate) set: String,
+    pub(crate) set_root: String,
+    pub(crate) background: Background,
+    pub(crate) background_root: String,
+    pub(crate) mask: Option<Mask>,
+    pub(crate) mask_root: String,
+    pub(crate) mask_border: Option<u32>,
+    pub(crate) format: Format,
+    #[serde(skip)]
+    pub(crate) asset_source: Arc<dyn AssetSource>,
+    pub(crate) symlinks: SymlinkPolicy,
+    pub(crate) catalogue: Option<Arc<Catalogue>>,
+    pub(crate) algorithm: Algorithm,
+    pub(crate) hash_input: HashInput,
+    pub(crate) parts: BTreeMap<String, usize>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            colour: None,
+            image_size: None,
+            set: String::from(SET_DEFAULT),
+            set_root: String::from("./sets"),
+            background: Background::None,
+            background_root: String::from("./backgrounds"),
+            mask: None,
+            mask_root: String::from("."),
+            mask_border: None,
+            format: Format::Png,
+            asset_source: asset::default_asset_source(),
+            symlinks: SymlinkPolicy::WithinRoot,
+            catalogue: None,
+            algorithm: Algorithm::V1,
+            hash_input: HashInput::Text,
+            parts: BTreeMap::new(),
+        }
+    }
+}
+
+impl PartialEq for RenderOptions {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            colour,
+            image_size,
+            set,
+            set_root,
+            background,
+            background_root,
+            mask,
+            mask_root,
+            mask_border,
+            format,
+            asset_source,
+            symlinks,
+            catalogue,
+            algorithm,
+            hash_input,
+            parts,
+        } = self;
+        *colour == other.colour
+            && *image_size == other.image_size
+            && *set == other.set
+            && *set_root == other.set_root
+            && *background == other.background
+            && *background_root == other.background_root
+            && *mask == other.mask
+            && *mask_root == other.mask_root
+            && *mask_border == other.mask_border
+            && *format == other.format
+            && asset::source_identity(asset_source) == asset::source_identity(&other.asset_source)
+            && *symlinks == other.symlinks
+            && *catalogue == other.catalogue
+            && *algorithm == other.algorithm
+            && *hash_input == other.hash_input
+            && *parts == other.parts
+    }
+}
+
+impl Eq for RenderOptions {}
+
+impl Hash for RenderOptions {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            colour,
+            image_size,
+            set,
+            set_root,
+            background,
+            background_root,
+            mask,
+            mask_root,
+            mask_border,
+            format,
+            asset_source,
+            symlinks,
+            catalogue,
+            algorithm,
+            hash_input,
+            parts,
+        } = self;
+        colour.hash(state);
+        image_size.hash(state);
+        set.hash(state);
+        set_root.hash(state);
+        background.hash(state);
+        background_root.hash(state);
+        mask.hash(state);
+        mask_root.hash(state);
+        mask_border.hash(state);
+        format.hash(state);
+        asset::source_identity(asset_source).hash(state);
+        symlinks.hash(state);
+        catalogue.hash(state);
+        algorithm.hash(state);
+        hash_input.hash(state);
+        parts.hash(state);
+    }
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_set(mut self, set: &str) -> Self {
+        self.set = String::from(set);
+        self
+    }
+
+    pub fn with_set_location(mut self, set_location: &str) -> Self {
+        self.set_root = String::from(set_location);
+        self
+    }
+
+    pub fn with_background_set(mut self, background_set: &str) -> Self {
+        self.background = Background::Set(String::from(background_set));
+        self
+    }
+
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn with_background_location(mut self, background_location: &str) -> Self {
+        self.background_root = String::from(background_location);
+        self
+    }
+
+    pub fn with_colour(mut self, colour: &str) -> Self {
+        self.colour = Some(String::from(colour));
+        self
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.image_size = Some(ImageSize { width, height });
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn with_mask_location(mut self, mask_location: &str) -> Self {
+        self.mask_root = String::from(mask_location);
+        self
+    }
+
+    pub fn with_mask_border(mut self, width: u32) -> Self {
+        self.mask_border = Some(width);
+        self
+    }
+
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_symlink_policy(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    pub fn with_catalogue(mut self, catalogue: Arc<Catalogue>) -> Self {
+        self.catalogue = Some(catalogue);
+        self
+    }
+
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.hash_input = HashInput::Seed(seed);
+        self
+    }
+
+    pub fn with_hash_array(mut self, hash_array: &[i64]) -> Self {
+        self.hash_input = HashInput::HashArray(hash_array.to_vec());
+        self
+    }
+
+    pub fn with_key(mut self, key: &[u8]) -> Self {
+        self.hash_input = HashInput::Keyed(Key(key.to_vec()));
+        self
+    }
+
+    pub fn with_parts(mut self, parts: &[(&str, usize)]) -> Self {
+        self.parts.extend(
+            parts
+                .iter()
+                .map(|(category, index)| (String::from(*category), *index)),
+        );
+        self
+    }
+
+    pub fn with_asset_source<S: AssetSource + 'static>(mut self, source: S) -> Self {
+        self.asset_source = Arc::new(source);
+        self
+    }
+}
@@ -0,0 +1,68 @@
+use image::Rgba;
+
+pub(crate) fn hash_colour(seed: i64, saturation: f32, lightness: f32) -> Rgba<u8> {
+    let hue = seed.rem_euclid(360) as f32;
+    hsl_to_rgba(hue, saturation, lightness)
+}
+
+pub(crate) fn hsl_to_rgba(hue: f32, saturation: f32, lightness: f32) -> Rgba<u8> {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |value: f32| ((value + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba([channel(r), channel(g), channel(b), 255])
+}
+
+pub(crate) fn blend_over(destination: &mut Rgba<u8>, source: Rgba<u8>, coverage: f32) {
+    let source_alpha = source[3] as f32 / 255.0 * coverage;
+    if source_alpha <= 0.0 {
+        return;
+    }
+    let destination_alpha = destination[3] as f32 / 255.0;
+    let alpha = source_alpha + destination_alpha * (1.0 - source_alpha);
+    for channel in 0..3 {
+        let value = (source[channel] as f32 * source_alpha
+            + destination[channel] as f32 * destination_alpha * (1.0 - source_alpha))
+            / alpha;
+        destination[channel] = value.round().clamp(0.0, 255.0) as u8;
+    }
+    destination[3] = (alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hsl_to_rgba_converts_primary_hues() {
+        // arrange
+        let expected_red = Rgba([255, 0, 0, 255]);
+        let expected_blue = Rgba([0, 0, 255, 255]);
+        // act
+        let red = hsl_to_rgba(0.0, 1.0, 0.5);
+        let blue = hsl_to_rgba(240.0, 1.0, 0.5);
+        // assert
+        assert_eq!(red, expected_red);
+        assert_eq!(blue, expected_blue)
+    }
+
+    #[test]
+    fn blend_over_a_transparent_pixel_returns_the_source_colour() {
+        // arrange
+        let mut destination = Rgba([0, 0, 0, 0]);
+        let source = Rgba([10, 20, 30, 255]);
+        // act
+        blend_over(&mut destination, source, 1.0);
+        // assert
+        assert_eq!(destination, source)
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::Profile;
+use crate::error::Error;
+use crate::options::Key;
+use crate::{materials, Background, ImageSize, RenderOptions, RoboHashGenerator, SetDefinition};
+
+#[derive(Debug, Default)]
+pub struct Tenant {
+    options: RenderOptions,
+    allowed_sets: BTreeSet<String>,
+    allowed_background_sets: BTreeSet<String>,
+    allowed_sizes: BTreeSet<(u32, u32)>,
+    key: Option<Key>,
+}
+
+impl Tenant {
+    pub fn new(options: RenderOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_allowed_sets(mut self, sets: &[&str]) -> Self {
+        self.allowed_sets = sets.iter().map(|set| String::from(*set)).collect();
+        self
+    }
+
+    pub fn with_allowed_background_sets(mut self, background_sets: &[&str]) -> Self {
+        self.allowed_background_sets = background_sets
+            .iter()
+            .map(|background_set| String::from(*background_set))
+            .collect();
+        self
+    }
+
+    pub fn with_allowed_sizes(mut self, sizes: &[(u32, u32)]) -> Self {
+        self.allowed_sizes = sizes.iter().copied().collect();
+        self
+    }
+
+    pub fn with_key(mut self, key: &[u8]) -> Self {
+        self.key = Some(Key(key.to_vec()));
+        self
+    }
+
+    fn options(&self, request: &Profile) -> Result<RenderOptions, Error> {
+        if request.set_root.is_some() || request.background_root.is_some() {
+            return Err(Error::NotAllowed(String::from("asset roots")));
+        }
+        let mut options = request.apply(self.options.clone())?;
+        let background_set = match &options.background {
+            Background::Set(background_set) => Some(background_set),
+            _ => None,
+        };
+        for name in [Some(&options.set), options.colour.as_ref(), background_set]
+            .into_iter()
+            .flatten()
+        {
+            materials::check_name(name)?;
+        }
+        if !self.allowed_sets.is_empty() && !self.allowed_sets.contains(&options.set) {
+            return Err(Error::NotAllowed(format!("set {}", options.set)));
+        }
+        if let Some(background_set) = background_set {
+            if !self.allowed_background_sets.is_empty()
+                && !self.allowed_background_sets.contains(background_set)
+            {
+                return Err(Error::NotAllowed(format!(
+                    "background set {background_set}"
+                )));
+            }
+        }
+        if !self.allowed_sizes.is_empty() {
+            let size = self.image_size(&options)?;
+            let size = (size.width, size.height);
+            if !self.allowed_sizes.contains(&size) {
+                return Err(Error::NotAllowed(format!("size {}x{}", size.0, size.1)));
+            }
+        }
+        if let Some(Key(key)) = &self.key {
+            options = options.with_key(key);
+        }
+        Ok(options)
+    }
+
+    fn image_size(&self, options: &RenderOptions) -> Result<ImageSize, Error> {
+        if let Some(size) = options.image_size {
+            return Ok(size);
+        }
+        let definition = SetDefinition::load(
+            options.asset_source.as_ref(),
+            options.symlinks,
+            &options.set_root,
+            &options.set,
+        )?;
+        Ok(definition
+            .as_ref()
+            .and_then(SetDefinition::image_size)
+            .unwrap_or_else(ImageSize::default))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProfileRegistry {
+    tenants: BTreeMap<String, Tenant>,
+    generator: RoboHashGenerator,
+}
+
+impl ProfileRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_generator(mut self, generator: RoboHashGenerator) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    pub fn with_tenant(mut self, id: &str, tenant: Tenant) -> Self {
+        self.tenants.insert(String::from(id), tenant);
+        self
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = &str> {
+        self.tenants.keys().map(String::as_str)
+    }
+
+    pub fn options(&self, tenant: &str, request: &Profile) -> Result<RenderOptions, Error> {
+        self.tenant(tenant)?.options(request)
+    }
+
+    pub fn render(&self, tenant: &str, text: &str, request: &Profile) -> Result<Vec<u8>, Error> {
+        let options = self.options(tenant, request)?;
+        self.generator.render_bytes(text, &options)
+    }
+
+    fn tenant(&self, tenant: &str) -> Result<&Tenant, Error> {
+        match self.tenants.get(tenant) {
+            Some(tenant) => Ok(tenant),
+            None => Err(Error::UnknownTenant(String::from(tenant))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::asset::MemoryAssetSource;
+    use crate::format::Format;
+    use crate::hash;
+    use crate::image::encode;
+    use crate::RoboHashBuilder;
+
+    fn assets(colour: [u8; 4]) -> MemoryAssetSource {
+        let part = encode(&RgbaImage::from_pixel(4, 4, Rgba(colour)), Format::Png).unwrap();
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("./sets/custom/000#a/000#a0.png"), part.clone());
+        assets.insert_file(Path::new("./sets/other/000#a/000#a0.png"), part.clone());
+        assets.insert_file(Path::new("./backgrounds/bg/000#bg0.png"), part);
+        assets
+    }
+
+    fn registry() -> ProfileRegistry {
+        let tenant = |colour| {
+            RenderOptions::new()
+                .with_asset_source(assets(colour))
+                .with_set("custom")
+                .with_size(8, 8)
+        };
+        ProfileRegistry::new()
+            .with_generator(RoboHashGenerator::new().with_part_cache_limit(1024 * 1024))
+            .with_tenant(
+                "red",
+                Tenant::new(tenant([255, 0, 0, 255]))
+                    .with_allowed_sets(&["custom"])
+                    .with_allowed_background_sets(&["bg"])
+                    .with_allowed_sizes(&[(8, 8), (16, 16)])
+                    .with_key(b"red secret"),
+            )
+            .with_tenant("blue", Tenant::new(tenant([0, 0, 255, 255])))
+    }
+
+    fn centre(bytes: &[u8]) -> Rgba<u8> {
+        *image::load_from_memory(bytes)
+            .unwrap()
+            .to_rgba8()
+            .get_pixel(4, 4)
+    }
+
+    #[test]
+    fn render_reads_only_the_tenants_assets() {
+        // arrange
+        let registry = registry();
+        let request = Profile::default();
+        // act
+        let red = registry.render("red", "test", &request).unwrap();
+        let blue = registry.render("blue", "test", &request).unwrap();
+        // assert
+        assert_eq!(centre(&red), Rgba([255, 0, 0, 255]));
+        assert_eq!(centre(&blue), Rgba([0, 0, 255, 255]))
+    }
+
+    #[test]
+    fn render_hashes_with_the_tenants_key() {
+        // arrange
+        let registry = registry();
+        // act
+        let options = registry.options("red", &Profile::default()).unwrap();
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_options(options)
+            .build()
+            .unwrap();
+        let unkeyed = RoboHashBuilder::new("test").build().unwrap();
+        // assert
+        let expected = RoboHashBuilder::new("test")
+            .with_key(b"red secret")
+            .build()
+            .unwrap();
+        assert_eq!(robo_hash.digest, hash::hmac_sha512(b"red secret", b"test"));
+        assert_eq!(robo_hash.hash_array, expected.hash_array);
+        assert_ne!(robo_hash.digest, unkeyed.digest)
+    }
+
+    #[test]
+    fn debug_output_redacts_the_tenants_key() {
+        // act
+        let debug = format!("{:?}", registry());
+        // assert
+        assert!(!debug.contains(&format!("{:?}", b"red secret")));
+        assert!(debug.contains("Key(..)"))
+    }
+
+    #[test]
+    fn render_rejects_requests_outside_of_the_tenants_limits() {
+        // arrange
+        let registry = registry();
+        let request = |profile: Profile| registry.options("red", &profile);
+        // act
+        let other_set = request(Profile {
+            set: Some(String::from("other")),
+            ..Profile::default()
+        });
+        let other_size = request(Profile {
+            size: Some(crate::SizeDefinition {
+                width: 1024,
+                height: 1024,
+            }),
+            ..Profile::default()
+        });
+        let other_background = request(Profile {
+            background_set: Some(String::from("other")),
+            ..Profile::default()
+        });
+        let allowed_background = registry.render(
+            "red",
+            "test",
+            &Profile {
+                background_set: Some(String::from("bg")),
+                ..Profile::default()
+            },
+        );
+        let other_root = request(Profile {
+            set_root: Some(String::from("/srv/tenants/blue/sets")),
+            ..Profile::default()
+        });
+        let traversal = registry.options(
+            "blue",
+            &Profile {
+                set: Some(String::from("../../blue/sets/custom")),
+                ..Profile::default()
+            },
+        );
+        let unknown = registry.render("green", "test", &Profile::default());
+        let default_size = ProfileRegistry::new()
+            .with_tenant(
+                "red",
+                Tenant::new(RenderOptions::new().with_asset_source(assets([255, 0, 0, 255])))
+                    .with_allowed_sizes(&[(8, 8)]),
+            )
+            .render("red", "test", &Profile::default());
+        // assert
+        assert!(matches!(other_set, Err(Error::NotAllowed(_))));
+        assert!(matches!(other_size, Err(Error::NotAllowed(_))));
+        assert!(matches!(other_background, Err(Error::NotAllowed(_))));
+        assert!(allowed_background.is_ok());
+        assert!(matches!(other_root, Err(Error::NotAllowed(_))));
+        assert!(matches!(traversal, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(unknown, Err(Error::UnknownTenant(_))));
+        assert!(matches!(default_size, Err(Error::NotAllowed(_))))
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::error::Error;
+#[cfg(feature = "render")]
+use crate::RoboHash;
+
+static TEMPORARY_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+pub struct RenderCache {
+    directory: PathBuf,
+    max_bytes: u64,
+    total_bytes: Arc<AtomicU64>,
+}
+
+impl RenderCache {
+    pub fn new(directory: &str, max_bytes: u64) -> Result<Self, Error> {
+        let directory = PathBuf::from(directory);
+        fs::create_dir_all(&directory)?;
+        let total_bytes = cached_entries(&directory)?
+            .iter()
+            .map(|(_, _, size)| size)
+            .sum::<u64>();
+        Ok(Self {
+            directory,
+            max_bytes,
+            total_bytes: Arc::new(AtomicU64::new(total_bytes)),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.entry_path(key)?;
+        match fs::read(&path) {
+            Ok(bytes) => {
+                if let Ok(file) = File::options().write(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                Ok(Some(bytes))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::IoError(e)),
+        }
+    }
+
+    pub fn put(&self, key: &str, bytes: &[u8]) -> Result<(), Error> {
+        let path = self.entry_path(key)?;
+        let replaced = fs::metadata(&path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        let temporary_path = self.directory.join(format!(
+            ".{key}.{}.{}.tmp",
+            std::process::id(),
+            TEMPORARY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = File::create(&temporary_path).and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        });
+        if let Err(e) = written.and_then(|_| fs::rename(&temporary_path, &path)) {
+            let _ = fs::remove_file(&temporary_path);
+            return Err(Error::IoError(e));
+        }
+        let resize = |total: u64| (total + bytes.len() as u64).saturating_sub(replaced);
+        let total_bytes =
+            match self
+                .total_bytes
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                    Some(resize(total))
+                }) {
+                Ok(previous) | Err(previous) => resize(previous),
+            };
+        if total_bytes > self.max_bytes {
+            self.evict()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "render")]
+    pub fn get_or_render(&self, robo_hash: &RoboHash) -> Result<Vec<u8>, Error> {
+        let key = robo_hash.cache_key()?;
+        if let Some(bytes) = self.get(&key)? {
+            return Ok(bytes);
+        }
+        let bytes = robo_hash.assemble()?;
+        self.put(&key, &bytes)?;
+        Ok(bytes)
+    }
+
+    fn entry_path(&self, key: &str) -> Result<PathBuf, Error> {
+        let plain = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !plain {
+            return Err(Error::InvalidCacheKey(String::from(key)));
+        }
+        Ok(self.directory.join(key))
+    }
+
+    fn evict(&self) -> Result<(), Error> {
+        let mut entries = cached_entries(&self.directory)?;
+        let mut total_bytes = entries.iter().map(|(_, _, size)| size).sum::<u64>();
+        entries.sort_by_key(|(_, modified, _)| *modified);
+        for (path, _, size) in entries {
+            if total_bytes <= self.max_bytes {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(_) => total_bytes -= size,
+                Err(e) if e.kind() == ErrorKind::NotFound => total_bytes -= size,
+                Err(e) => return Err(Error::IoError(e)),
+            }
+        }
+        self.total_bytes.store(total_bytes, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn cached_entries(directory: &Path) -> Result<Vec<(PathBuf, SystemTime, u64)>, Error> {
+    let entries = directory
+        .read_dir()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            Some((entry.path(), modified, metadata.len()))
+        })
+        .collect::<Vec<(PathBuf, SystemTime, u64)>>();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    #[cfg(feature = "render")]
+    use crate::RoboHashBuilder;
+
+    fn cache_directory(name: &str) -> String {
+        let directory = std::env::temp_dir().join(format!(
+            "robohash-render-cache-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        directory.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn get_returns_what_was_put() {
+        // arrange
+        let cache = RenderCache::new(&cache_directory("get"), 1024).unwrap();
+        // act
+        cache.put("key", &[1, 2, 3]).unwrap();
+        // assert
+        assert_eq!(cache.get("key").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(cache.get("missing").unwrap(), None)
+    }
+
+    #[test]
+    fn get_and_put_reject_keys_that_are_not_plain_names() {
+        // arrange
+        let cache = RenderCache::new(&cache_directory("keys"), 1024).unwrap();
+        // act
+        let keys = ["../../x", "a/b", ".hidden", ""]
+            .map(|key| (cache.get(key).is_err(), cache.put(key, &[1]).is_err()));
+        // assert
+        assert!(keys.iter().all(|rejected| *rejected == (true, true)))
+    }
+
+    #[test]
+    fn put_keeps_a_running_total_of_the_cached_bytes() {
+        // arrange
+        let directory = cache_directory("total");
+        let cache = RenderCache::new(&directory, 1024).unwrap();
+        cache.put("first", &[0; 4]).unwrap();
+        // act
+        cache.put("first", &[0; 6]).unwrap();
+        cache.put("second", &[0; 2]).unwrap();
+        // assert
+        assert_eq!(cache.total_bytes.load(Ordering::Relaxed), 8);
+        let reopened = RenderCache::new(&directory, 1024).unwrap();
+        assert_eq!(reopened.total_bytes.load(Ordering::Relaxed), 8)
+    }
+
+    #[test]
+    fn put_evicts_least_recently_used_entries_over_the_size_limit() {
+        // arrange
+        let cache = RenderCache::new(&cache_directory("evict"), 8).unwrap();
+        cache.put("first", &[0; 4]).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        cache.put("second", &[0; 4]).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        // act
+        cache.put("third", &[0; 4]).unwrap();
+        // assert
+        assert_eq!(cache.get("first").unwrap(), None);
+        assert!(cache.get("second").unwrap().is_some());
+        assert!(cache.get("third").unwrap().is_some())
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn get_or_render_stores_the_rendered_image_under_the_cache_key() {
+        // arrange
+        let cache = RenderCache::new(&cache_directory("render"), 1 << 20).unwrap();
+        let robo_hash = RoboHashBuilder::new("test")
+            .with_set("set4")
+            .with_size(32, 32)
+            .with_catalogue(crate::tests::catalogue())
+            .build()
+            .unwrap();
+        // act
+        let rendered = cache.get_or_render(&robo_hash).unwrap();
+        // assert
+        let key = robo_hash.cache_key().unwrap();
+        assert_eq!(cache.get(&key).unwrap(), Some(rendered))
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hash::Hasher;
+
+const PRESENCE_SHIFT: u32 = 24;
+const PRESENCE_RESOLUTION: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Algorithm {
+    #[default]
+    V1,
+    V2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Slot {
+    Colour,
+    Background,
+    Part(usize),
+    Presence(usize),
+}
+
+impl Slot {
+    fn hash_index(&self) -> usize {
+        match self {
+            Slot::Colour => 0,
+            Slot::Background => 3,
+            Slot::Part(index) | Slot::Presence(index) => *index,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Slot::Colour => String::from("colour"),
+            Slot::Background => String::from("background"),
+            Slot::Part(index) => format!("part:{index}"),
+            Slot::Presence(index) => format!("presence:{index}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Selector<'a> {
+    algorithm: Algorithm,
+    digest: &'a str,
+    hash_array: &'a [i64],
+    parts: Option<&'a BTreeMap<String, usize>>,
+}
+
+impl<'a> Selector<'a> {
+    pub(crate) fn new(algorithm: Algorithm, digest: &'a str, hash_array: &'a [i64]) -> Self {
+        Self {
+            algorithm,
+            digest,
+            hash_array,
+            parts: None,
+        }
+    }
+
+    pub(crate) fn with_parts(mut self, parts: &'a BTreeMap<String, usize>) -> Self {
+        self.parts = Some(parts);
+        self
+    }
+
+    pub(crate) fn is_overridden(&self, category: &str) -> bool {
+        self.part_override(category).is_some()
+    }
+
+    pub(crate) fn overridden_part(&self, category: &str, len: usize) -> Option<usize> {
+        let index = self.part_override(category)?;
+        if len == 0 {
+            return None;
+        }
+        Some(index % len)
+    }
+
+    fn part_override(&self, category: &str) -> Option<usize> {
+        let parts = self.parts?;
+        match parts.get(category) {
+            Some(index) => Some(*index),
+            None => parts.get(category.split_once('#')?.1).copied(),
+        }
+    }
+
+    pub(crate) fn index(&self, slot: Slot, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        match self.algorithm {
+            Algorithm::V1 => {
+                let hash_value = self.hash_array.get(slot.hash_index())?;
+                Some(hash_value.rem_euclid(len as i64) as usize)
+            }
+            Algorithm::V2 => Some(self.uniform(slot, len as u64) as usize),
+        }
+    }
+
+    pub(crate) fn is_present(&self, slot: Slot, probability: f64) -> Option<bool> {
+        let roll = match self.algorithm {
+            Algorithm::V1 => {
+                let hash_value = self.hash_array.get(slot.hash_index())?;
+                (hash_value >> PRESENCE_SHIFT) as u64 % PRESENCE_RESOLUTION
+            }
+            Algorithm::V2 => self.uniform(slot, PRESENCE_RESOLUTION),
+        };
+        Some((roll as f64) < probability * PRESENCE_RESOLUTION as f64)
+    }
+
+    fn uniform(&self, slot: Slot, len: u64) -> u64 {
+        let label = slot.label();
+        let threshold = len.wrapping_neg() % len;
+        let mut attempt = 0_u32;
+        loop {
+            let product = self.draw(&label, attempt) as u128 * len as u128;
+            if product as u64 >= threshold {
+                return (product >> 64) as u64;
+            }
+            attempt += 1;
+        }
+    }
+
+    fn draw(&self, label: &str, attempt: u32) -> u64 {
+        let mut hasher = Hasher::new();
+        hasher.update(self.digest.as_bytes());
+        hasher.update(label.as_bytes());
+        hasher.update(&attempt.to_be_bytes());
+        let bytes = hasher.finish();
+        let mut draw = [0; 8];
+        draw.copy_from_slice(&bytes[..8]);
+        u64::from_be_bytes(draw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_v1_matches_the_legacy_modulo_selection() {
+        // arrange
+        let hash_array = [7, 0, 0, 11, 23];
+        let selector = Selector::new(Algorithm::V1, "", &hash_array);
+        // act
+        let colour = selector.index(Slot::Colour, 5);
+        let background = selector.index(Slot::Background, 5);
+        let part = selector.index(Slot::Part(4), 5);
+        // assert
+        assert_eq!((colour, background, part), (Some(2), Some(1), Some(3)))
+    }
+
+    #[test]
+    fn index_returns_none_for_empty_choices_and_missing_hash_index() {
+        // arrange
+        let selector = Selector::new(Algorithm::V1, "", &[1, 2]);
+        // act
+        let empty = selector.index(Slot::Colour, 0);
+        let missing = selector.index(Slot::Part(2), 3);
+        // assert
+        assert_eq!((empty, missing), (None, None))
+    }
+
+    #[test]
+    fn overridden_part_matches_directory_or_name_and_wraps_around() {
+        // arrange
+        let parts = BTreeMap::from([(String::from("00body"), 7), (String::from("hat"), 1)]);
+        let selector = Selector::new(Algorithm::V1, "", &[]).with_parts(&parts);
+        // act
+        let body = selector.overridden_part("000#00body", 5);
+        let hat = selector.overridden_part("hat", 5);
+        let eyes = selector.overridden_part("002#02eyes", 5);
+        // assert
+        assert_eq!((body, hat, eyes), (Some(2), Some(1), None))
+    }
+
+    #[test]
+    fn index_v2_is_deterministic_and_independent_of_the_hash_array() {
+        // arrange
+        let selector = Selector::new(Algorithm::V2, "digest", &[]);
+        // act
+        let first = selector.index(Slot::Part(30), 7);
+        let second = selector.index(Slot::Part(30), 7);
+        // assert
+        assert!(first.is_some());
+        assert_eq!(first, second)
+    }
+
+    #[test]
+    fn index_v2_distributes_choices_uniformly() {
+        // arrange
+        let digests = (0..3000).map(|i| format!("{i}")).collect::<Vec<String>>();
+        let mut counts = [0; 3];
+        // act
+        for digest in &digests {
+            let selector = Selector::new(Algorithm::V2, digest, &[]);
+            counts[selector.index(Slot::Part(4), 3).unwrap()] += 1;
+        }
+        // assert
+        assert!(counts.iter().all(|count| (900..1100).contains(count)))
+    }
+}
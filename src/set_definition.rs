@@ -0,0 +1,499 @@
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::asset::{AssetSource, SymlinkPolicy};
+use crate::error::Error;
+use crate::materials;
+use crate::selection::{Selector, Slot};
+use crate::ImageSize;
+
+const FIRST_CATEGORY_HASH_INDEX: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SetDefinition {
+    pub categories: Vec<CategoryDefinition>,
+    #[serde(default)]
+    pub colours: Vec<String>,
+    #[serde(default)]
+    pub default_size: Option<SizeDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CategoryDefinition {
+    pub directory: String,
+    #[serde(default)]
+    pub z_index: Option<i32>,
+    #[serde(default)]
+    pub hash_index: Option<usize>,
+    #[serde(default)]
+    pub presence: Option<f64>,
+    #[serde(default)]
+    pub weights: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Selection {
+    pub(crate) parts: Vec<String>,
+    pub(crate) omitted: Vec<String>,
+}
+
+// Presence compares by its bits, like it hashes, so definitions can be map keys.
+impl PartialEq for CategoryDefinition {
+    fn eq(&self, other: &Self) -> bool {
+        self.directory == other.directory
+            && self.z_index == other.z_index
+            && self.hash_index == other.hash_index
+            && self.presence_bits() == other.presence_bits()
+            && self.weights == other.weights
+    }
+}
+
+impl Eq for CategoryDefinition {}
+
+impl Hash for CategoryDefinition {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.directory.hash(state);
+        self.z_index.hash(state);
+        self.hash_index.hash(state);
+        self.presence_bits().hash(state);
+        self.weights.hash(state);
+    }
+}
+
+impl CategoryDefinition {
+    fn presence_bits(&self) -> Option<u64> {
+        self.presence.map(|presence| (presence + 0.0).to_bits())
+    }
+
+    fn is_present(&self, selector: &Selector, hash_index: usize) -> Result<bool, Error> {
+        let presence = self.presence.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&presence) {
+            return Err(Error::InvalidSetDefinition(format!(
+                "presence {presence} of category {} is not between 0 and 1",
+                self.directory
+            )));
+        }
+        match selector.is_present(Slot::Presence(hash_index), presence) {
+            Some(present) => Ok(present),
+            None => Err(self.hash_index_out_of_range(hash_index)),
+        }
+    }
+
+    fn select<'a>(
+        &self,
+        selector: &Selector,
+        hash_index: usize,
+        files: &'a [String],
+    ) -> Result<&'a String, Error> {
+        if let Some(name) = self
+            .weights
+            .keys()
+            .find(|name| !files.iter().any(|file| part_name(file) == **name))
+        {
+            return Err(Error::InvalidSetDefinition(format!(
+                "weight for unknown part {name} of category {}",
+                self.directory
+            )));
+        }
+        let weights = files
+            .iter()
+            .map(|file| *self.weights.get(&part_name(file)).unwrap_or(&1) as usize)
+            .collect::<Vec<usize>>();
+        let total = weights.iter().sum::<usize>();
+        if total == 0 {
+            return Err(Error::InvalidSetDefinition(format!(
+                "parts of category {} have no weight",
+                self.directory
+            )));
+        }
+        let mut ticket = match selector.index(Slot::Part(hash_index), total) {
+            Some(ticket) => ticket,
+            None => return Err(self.hash_index_out_of_range(hash_index)),
+        };
+        for (file, weight) in files.iter().zip(weights) {
+            if ticket < weight {
+                return Ok(file);
+            }
+            ticket -= weight;
+        }
+        Err(Error::InvalidSetDefinition(format!(
+            "failed to select a part of category {}",
+            self.directory
+        )))
+    }
+
+    fn hash_index_out_of_range(&self, hash_index: usize) -> Error {
+        Error::InvalidSetDefinition(format!(
+            "hash index {hash_index} of category {} is out of range",
+            self.directory
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SizeDefinition {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SetDefinition {
+    pub fn from_toml(definition: &str) -> Result<Self, Error> {
+        match toml::from_str(definition) {
+            Ok(definition) => Ok(definition),
+            Err(e) => Err(Error::InvalidSetDefinition(format!("{e}"))),
+        }
+    }
+
+    pub fn from_json(definition: &str) -> Result<Self, Error> {
+        match serde_json::from_str(definition) {
+            Ok(definition) => Ok(definition),
+            Err(e) => Err(Error::InvalidSetDefinition(format!("{e}"))),
+        }
+    }
+
+    pub(crate) fn load(
+        source: &dyn AssetSource,
+        symlinks: SymlinkPolicy,
+        sets_root: &str,
+        set: &str,
+    ) -> Result<Option<Self>, Error> {
+        let set_directory = Path::new(sets_root).join(set);
+        let read = |file| read_definition(source, symlinks, sets_root, &set_directory.join(file));
+        if let Some(definition) = read("set.toml")? {
+            return Self::from_toml(&definition).map(Some);
+        }
+        if let Some(definition) = read("set.json")? {
+            return Self::from_json(&definition).map(Some);
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn image_size(&self) -> Option<ImageSize> {
+        self.default_size.map(|size| ImageSize {
+            width: size.width,
+            height: size.height,
+        })
+    }
+
+    pub(crate) fn colour(
+        &self,
+        selector: &Selector,
+        set: &str,
+        colour: &Option<String>,
+    ) -> Result<Option<String>, Error> {
+        match colour {
+            Some(colour) => {
+                self.check_colour(set, colour)?;
+                Ok(Some(colour.to_owned()))
+            }
+            None if self.colours.is_empty() => Ok(None),
+            None => Ok(selector
+                .index(Slot::Colour, self.colours.len())
+                .map(|selected_index| self.colours[selected_index].to_owned())),
+        }
+    }
+
+    pub(crate) fn check_colour(&self, set: &str, colour: &str) -> Result<(), Error> {
+        if !self.colours.iter().any(|declared| declared == colour) {
+            return Err(Error::UnknownAsset(format!("{set}/{colour}")));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn files_in_set(
+        &self,
+        source: &dyn AssetSource,
+        selector: &Selector,
+        sets_root: &str,
+        set: &str,
+    ) -> Result<Selection, Error> {
+        let mut parts = Vec::with_capacity(self.categories.len());
+        let mut omitted = Vec::new();
+        for (position, category) in self.categories.iter().enumerate() {
+            let hash_index = category
+                .hash_index
+                .unwrap_or(FIRST_CATEGORY_HASH_INDEX + position);
+            if !selector.is_overridden(&category.directory)
+                && !category.is_present(selector, hash_index)?
+            {
+                omitted.push(category.directory.to_owned());
+                continue;
+            }
+            let files = materials::files_in_category(source, sets_root, set, &category.directory)?;
+            if files.is_empty() {
+                return Err(Error::InvalidSetDefinition(format!(
+                    "category {} has no parts",
+                    category.directory
+                )));
+            }
+            let selected_file = match selector.overridden_part(&category.directory, files.len()) {
+                Some(index) => &files[index],
+                None => category.select(selector, hash_index, &files)?,
+            };
+            let z_index = category.z_index.unwrap_or(position as i32);
+            parts.push((z_index, selected_file.to_owned()));
+        }
+        parts.sort_by_key(|(z_index, _)| *z_index);
+        Ok(Selection {
+            parts: parts.into_iter().map(|(_, part)| part).collect(),
+            omitted,
+        })
+    }
+}
+
+fn part_name(file: &str) -> String {
+    Path::new(file)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn read_definition(
+    source: &dyn AssetSource,
+    symlinks: SymlinkPolicy,
+    sets_root: &str,
+    file: &Path,
+) -> Result<Option<String>, Error> {
+    match materials::confine(source, symlinks, sets_root, &file.to_string_lossy()) {
+        Err(e) if e.is_not_found() => return Ok(None),
+        confined => confined?,
+    }
+    match source.read(file) {
+        Ok(definition) => Ok(Some(String::from_utf8_lossy(&definition).into_owned())),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::MemoryAssetSource;
+    use crate::selection::Algorithm;
+
+    fn selector(hash_array: &[i64]) -> Selector<'_> {
+        Selector::new(Algorithm::V1, "", hash_array)
+    }
+
+    const DEFINITION: &str = r#"
+        colours = ["red", "blue"]
+        default_size = { width = 300, height = 200 }
+
+        [[categories]]
+        directory = "hat"
+        z_index = 10
+
+        [[categories]]
+        directory = "body"
+        z_index = 0
+        hash_index = 9
+    "#;
+
+    #[test]
+    fn from_toml_reads_categories_colours_and_default_size() {
+        // act
+        let definition = SetDefinition::from_toml(DEFINITION).unwrap();
+        // assert
+        assert_eq!(definition.categories.len(), 2);
+        assert_eq!(definition.categories[1].hash_index, Some(9));
+        assert_eq!(definition.colours, vec!["red", "blue"]);
+        assert_eq!(
+            definition.default_size,
+            Some(SizeDefinition {
+                width: 300,
+                height: 200
+            })
+        )
+    }
+
+    #[test]
+    fn from_json_reads_the_same_definition() {
+        // arrange
+        let json = r#"{
+            "colours": ["red", "blue"],
+            "default_size": { "width": 300, "height": 200 },
+            "categories": [
+                { "directory": "hat", "z_index": 10 },
+                { "directory": "body", "z_index": 0, "hash_index": 9 }
+            ]
+        }"#;
+        // act
+        let definition = SetDefinition::from_json(json).unwrap();
+        // assert
+        assert_eq!(definition, SetDefinition::from_toml(DEFINITION).unwrap())
+    }
+
+    #[test]
+    fn from_toml_returns_invalid_set_definition_error_for_unknown_fields() {
+        // act
+        let definition = SetDefinition::from_toml("categories = []\nlayers = 3");
+        // assert
+        assert!(matches!(definition, Err(Error::InvalidSetDefinition(_))))
+    }
+
+    #[test]
+    fn files_in_set_orders_parts_by_z_index_and_uses_declared_hash_index() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("sets/custom/red/hat/a.png"), vec![]);
+        assets.insert_file(Path::new("sets/custom/red/body/a.png"), vec![]);
+        assets.insert_file(Path::new("sets/custom/red/body/b.png"), vec![]);
+        let definition = SetDefinition::from_toml(DEFINITION).unwrap();
+        let mut hash_array = vec![0; 22];
+        hash_array[9] = 1;
+        // act
+        let parts = definition.files_in_set(&assets, &selector(&hash_array), "sets", "custom/red");
+        // assert
+        assert_eq!(
+            parts.unwrap().parts,
+            vec!["sets/custom/red/body/b.png", "sets/custom/red/hat/a.png"]
+        )
+    }
+
+    #[test]
+    fn files_in_set_omits_categories_according_to_presence() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("sets/custom/hat/a.png"), vec![]);
+        let definition =
+            SetDefinition::from_toml("[[categories]]\ndirectory = \"hat\"\npresence = 0.3")
+                .unwrap();
+        let hash_values = (0..1000_i64).map(|i| (i * 7_919) << 24);
+        // act
+        let present = hash_values
+            .map(|hash_value| {
+                let mut hash_array = vec![0; 22];
+                hash_array[4] = hash_value;
+                definition
+                    .files_in_set(&assets, &selector(&hash_array), "sets", "custom")
+                    .unwrap()
+            })
+            .filter(|selection| selection.omitted.is_empty())
+            .count();
+        // assert
+        assert!((250..350).contains(&present))
+    }
+
+    #[test]
+    fn files_in_set_selects_parts_according_to_weights() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("sets/custom/hat/000#common.png"), vec![]);
+        assets.insert_file(Path::new("sets/custom/hat/001#legendary.png"), vec![]);
+        let definition = SetDefinition::from_toml(
+            "[[categories]]\ndirectory = \"hat\"\nweights = { \"000#common.png\" = 9 }",
+        )
+        .unwrap();
+        // act
+        let legendary = (0..1000_i64)
+            .map(|hash_value| {
+                let mut hash_array = vec![0; 22];
+                hash_array[4] = hash_value;
+                definition
+                    .files_in_set(&assets, &selector(&hash_array), "sets", "custom")
+                    .unwrap()
+            })
+            .filter(|selection| selection.parts[0].ends_with("legendary.png"))
+            .count();
+        // assert
+        assert_eq!(legendary, 100)
+    }
+
+    #[test]
+    fn files_in_set_returns_error_for_weights_of_unknown_parts() {
+        // arrange
+        let mut assets = MemoryAssetSource::new();
+        assets.insert_file(Path::new("sets/custom/hat/000#common.png"), vec![]);
+        let definition = SetDefinition::from_toml(
+            "[[categories]]\ndirectory = \"hat\"\nweights = { \"000#comon.png\" = 9 }",
+        )
+        .unwrap();
+        // act
+        let parts = definition.files_in_set(&assets, &selector(&[0; 22]), "sets", "custom");
+        // assert
+        assert!(matches!(parts, Err(Error::InvalidSetDefinition(_))))
+    }
+
+    #[test]
+    fn colour_returns_error_for_colours_the_definition_does_not_declare() {
+        // arrange
+        let definition = SetDefinition::from_toml(DEFINITION).unwrap();
+        let uncoloured = SetDefinition::from_toml("[[categories]]\ndirectory = \"hat\"").unwrap();
+        let colour = |definition: &SetDefinition, colour: &str| {
+            definition.colour(&selector(&[0; 22]), "custom", &Some(String::from(colour)))
+        };
+        // act
+        let declared = colour(&definition, "blue");
+        let undeclared = colour(&definition, "green");
+        let uncoloured = colour(&uncoloured, "blue");
+        // assert
+        assert_eq!(declared.unwrap().as_deref(), Some("blue"));
+        assert!(matches!(undeclared, Err(Error::UnknownAsset(_))));
+        assert!(matches!(uncoloured, Err(Error::UnknownAsset(_))))
+    }
+
+    #[test]
+    fn files_in_set_returns_error_for_presence_outside_of_zero_and_one() {
+        // arrange
+        let assets = MemoryAssetSource::new();
+        let definition =
+            SetDefinition::from_toml("[[categories]]\ndirectory = \"hat\"\npresence = 1.5")
+                .unwrap();
+        // act
+        let parts = definition.files_in_set(&assets, &selector(&[0; 22]), "sets", "custom");
+        // assert
+        assert!(matches!(parts, Err(Error::InvalidSetDefinition(_))))
+    }
+
+    #[test]
+    fn files_in_set_returns_error_for_out_of_range_hash_index() {
+        // arrange
+        let assets = MemoryAssetSource::new();
+        let definition =
+            SetDefinition::from_toml("[[categories]]\ndirectory = \"body\"\nhash_index = 22")
+                .unwrap();
+        // act
+        let parts = definition.files_in_set(&assets, &selector(&[0; 22]), "sets", "custom");
+        // assert
+        assert!(matches!(parts, Err(Error::InvalidSetDefinition(_))))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn load_applies_the_symlink_policy_to_the_definition_file() {
+        // arrange
+        let directory =
+            std::env::temp_dir().join(format!("robohash-definition-{}", std::process::id()));
+        let sets_root = directory.join("sets");
+        std::fs::create_dir_all(sets_root.join("linked")).unwrap();
+        std::fs::write(directory.join("set.toml"), DEFINITION).unwrap();
+        std::os::unix::fs::symlink(
+            directory.join("set.toml"),
+            sets_root.join("linked/set.toml"),
+        )
+        .unwrap();
+        let load = |symlinks| {
+            SetDefinition::load(
+                &crate::FileSystemAssetSource,
+                symlinks,
+                sets_root.to_str().unwrap(),
+                "linked",
+            )
+        };
+        // act
+        let deny = load(SymlinkPolicy::Deny);
+        let within_root = load(SymlinkPolicy::WithinRoot);
+        let follow = load(SymlinkPolicy::Follow);
+        std::fs::remove_dir_all(&directory).unwrap();
+        // assert
+        assert!(matches!(deny, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(within_root, Err(Error::AssetOutsideRoot(_))));
+        assert!(matches!(follow, Ok(Some(_))))
+    }
+}
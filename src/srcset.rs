@@ -0,0 +1,73 @@
+use crate::Format;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub width: u32,
+    pub height: u32,
+    pub format: Format,
+    pub base64: String,
+}
+
+impl Variant {
+    pub fn data_uri(&self) -> String {
+        format!("data:{};base64,{}", self.format.mime_type(), self.base64)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variants {
+    variants: Vec<Variant>,
+}
+
+impl Variants {
+    pub(crate) fn new(variants: Vec<Variant>) -> Self {
+        Self { variants }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Variant> {
+        self.variants.iter()
+    }
+
+    pub fn get(&self, width: u32) -> Option<&Variant> {
+        self.variants.iter().find(|variant| variant.width == width)
+    }
+
+    pub fn into_vec(self) -> Vec<Variant> {
+        self.variants
+    }
+
+    pub fn srcset<F>(&self, url: F) -> String
+    where
+        F: Fn(&Variant) -> String,
+    {
+        self.variants
+            .iter()
+            .map(|variant| format!("{} {}w", url(variant), variant.width))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(width: u32) -> Variant {
+        Variant {
+            width,
+            height: width,
+            format: Format::Png,
+            base64: String::from("AAAA"),
+        }
+    }
+
+    #[test]
+    fn srcset_lists_every_variant_with_its_width_descriptor() {
+        // arrange
+        let variants = Variants::new(vec![variant(32), variant(64)]);
+        // act
+        let srcset = variants.srcset(|variant| format!("/avatar/test?size={}", variant.width));
+        // assert
+        assert_eq!(srcset, "/avatar/test?size=32 32w, /avatar/test?size=64 64w")
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+use crate::error::Error;
+
+const BLESS: &str = "BLESS";
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub dimensions: (u32, u32),
+    pub expected_dimensions: (u32, u32),
+    pub differing_pixels: usize,
+    pub max_difference: u8,
+    diff: RgbaImage,
+}
+
+impl Comparison {
+    pub fn matches(&self) -> bool {
+        self.dimensions == self.expected_dimensions && self.differing_pixels == 0
+    }
+
+    pub fn write_diff(&self, path: &Path) -> Result<(), Error> {
+        self.diff.save(path)?;
+        Ok(())
+    }
+}
+
+pub fn compare_images(actual: &[u8], expected: &[u8], tolerance: u8) -> Result<Comparison, Error> {
+    let actual = image::load_from_memory(actual)?.to_rgba8();
+    let expected = image::load_from_memory(expected)?.to_rgba8();
+    let width = actual.width().min(expected.width());
+    let height = actual.height().min(expected.height());
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    let diff = RgbaImage::from_fn(width, height, |x, y| {
+        let actual = actual.get_pixel(x, y);
+        let expected = expected.get_pixel(x, y);
+        let difference = actual
+            .0
+            .iter()
+            .zip(expected.0)
+            .map(|(actual, expected)| actual.abs_diff(expected))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            differing_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let luma = (expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 3;
+            let faded = (luma * expected[3] as u32 / 255 / 4) as u8;
+            Rgba([faded, faded, faded, 255])
+        }
+    });
+    Ok(Comparison {
+        dimensions: actual.dimensions(),
+        expected_dimensions: expected.dimensions(),
+        differing_pixels,
+        max_difference,
+        diff,
+    })
+}
+
+pub fn assert_golden_image(actual: &[u8], fixture: impl AsRef<Path>, tolerance: u8) {
+    let fixture = fixture.as_ref();
+    if std::env::var(BLESS).is_ok_and(|bless| bless == "1") {
+        if let Err(e) = bless(actual, fixture) {
+            panic!("failed to bless {fixture:?}: {e}");
+        }
+        return;
+    }
+    let expected = match std::fs::read(fixture) {
+        Ok(expected) => expected,
+        Err(e) => panic!("failed to read {fixture:?}: {e}, run with {BLESS}=1 to create it"),
+    };
+    let comparison = match compare_images(actual, &expected, tolerance) {
+        Ok(comparison) => comparison,
+        Err(e) => panic!("failed to compare against {fixture:?}: {e}"),
+    };
+    if comparison.matches() {
+        return;
+    }
+    let diff_path = diff_path(fixture);
+    let _ = comparison.write_diff(&diff_path);
+    panic!(
+        "{fixture:?} does not match: {}x{} vs expected {}x{}, {} pixels differ by more than {tolerance} (max {}), diff written to {diff_path:?}, run with {BLESS}=1 to accept",
+        comparison.dimensions.0,
+        comparison.dimensions.1,
+        comparison.expected_dimensions.0,
+        comparison.expected_dimensions.1,
+        comparison.differing_pixels,
+        comparison.max_difference,
+    )
+}
+
+fn bless(actual: &[u8], fixture: &Path) -> Result<(), Error> {
+    if let Some(parent) = fixture.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image::load_from_memory(actual)?.save(fixture)?;
+    Ok(())
+}
+
+fn diff_path(fixture: &Path) -> PathBuf {
+    let stem = fixture
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    fixture.with_file_name(format!("{stem}.diff.png"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::image::encode;
+
+    fn encoded(pixel: [u8; 4]) -> Vec<u8> {
+        encode(&RgbaImage::from_pixel(4, 4, Rgba(pixel)), Format::Png).unwrap()
+    }
+
+    #[test]
+    fn compare_images_accepts_differences_within_the_tolerance() {
+        // act
+        let comparison = compare_images(
+            &encoded([100, 100, 100, 255]),
+            &encoded([102, 99, 100, 255]),
+            2,
+        );
+        // assert
+        assert!(comparison.unwrap().matches())
+    }
+
+    #[test]
+    fn compare_images_counts_pixels_outside_of_the_tolerance() {
+        // act
+        let comparison = compare_images(
+            &encoded([100, 100, 100, 255]),
+            &encoded([110, 100, 100, 255]),
+            2,
+        )
+        .unwrap();
+        // assert
+        assert!(!comparison.matches());
+        assert_eq!(comparison.differing_pixels, 16);
+        assert_eq!(comparison.max_difference, 10)
+    }
+
+    #[test]
+    fn compare_images_rejects_different_dimensions() {
+        // arrange
+        let larger = encode(&RgbaImage::new(8, 8), Format::Png).unwrap();
+        // act
+        let comparison = compare_images(&encoded([0, 0, 0, 0]), &larger, 0);
+        // assert
+        assert!(!comparison.unwrap().matches())
+    }
+
+    #[test]
+    fn diff_path_is_written_next_to_the_fixture() {
+        // act
+        let path = diff_path(Path::new("test_resources/golden/v1/test.png"));
+        // assert
+        assert_eq!(path, Path::new("test_resources/golden/v1/test.diff.png"))
+    }
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "render")]
+
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{ImageOutputFormat, Rgba, RgbaImage};
+use proptest::prelude::*;
+use robohash::{Algorithm, Background, MemoryAssetSource, RoboHashBuilder};
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Part(String),
+    Garbage(String),
+    Directory(String),
+}
+
+#[derive(Debug, Clone)]
+struct Tree {
+    set: String,
+    categories: Vec<(String, Vec<Entry>)>,
+    backgrounds: Option<Vec<String>>,
+}
+
+fn png() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))
+        .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+fn name() -> impl Strategy<Value = String> {
+    "[a-z0-9#.]{0,6}"
+}
+
+fn entry() -> impl Strategy<Value = Entry> {
+    prop_oneof![
+        4 => name().prop_map(Entry::Part),
+        1 => name().prop_map(Entry::Garbage),
+        1 => name().prop_map(Entry::Directory),
+    ]
+}
+
+fn tree() -> impl Strategy<Value = Tree> {
+    (
+        prop_oneof![Just(String::from("set1")), Just(String::from("custom"))],
+        prop::collection::vec((name(), prop::collection::vec(entry(), 0..4)), 0..24),
+        prop::option::of(prop::collection::vec(name(), 0..3)),
+    )
+        .prop_map(|(set, categories, backgrounds)| Tree {
+            set,
+            categories,
+            backgrounds,
+        })
+}
+
+fn assets(tree: &Tree) -> MemoryAssetSource {
+    let part = png();
+    let mut assets = MemoryAssetSource::new();
+    let set = Path::new("sets").join(&tree.set);
+    assets.insert_directory(&set, Vec::new());
+    for (category, entries) in &tree.categories {
+        let category = set.join(category);
+        assets.insert_directory(&category, Vec::new());
+        for entry in entries {
+            match entry {
+                Entry::Part(file) => assets.insert_file(&category.join(file), part.clone()),
+                Entry::Garbage(file) => assets.insert_file(&category.join(file), vec![0; 3]),
+                Entry::Directory(directory) => {
+                    assets.insert_directory(&category.join(directory), Vec::new())
+                }
+            }
+        }
+    }
+    if let Some(backgrounds) = &tree.backgrounds {
+        let directory = Path::new("backgrounds/bg");
+        assets.insert_directory(directory, Vec::new());
+        for background in backgrounds {
+            assets.insert_file(&directory.join(background), part.clone());
+        }
+    }
+    assets
+}
+
+fn background() -> impl Strategy<Value = Background> {
+    prop_oneof![
+        Just(Background::None),
+        Just(Background::Set(String::from("bg"))),
+        Just(Background::HashDerived),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn rendering_a_random_set_tree_returns_an_image_or_an_error(
+        tree in tree(),
+        hash_array in prop::option::of(prop::collection::vec(any::<i64>(), 0..30)),
+        algorithm in prop_oneof![Just(Algorithm::V1), Just(Algorithm::V2)],
+        background in background(),
+        size in (0_u32..24, 0_u32..24),
+        border in prop::option::of(0_u32..4),
+    ) {
+        // arrange
+        let mut builder = RoboHashBuilder::new("test")
+            .with_set(&tree.set)
+            .with_set_location("sets")
+            .with_background_location("backgrounds")
+            .with_background(background)
+            .with_algorithm(algorithm)
+            .with_size(size.0, size.1)
+            .with_asset_source(assets(&tree));
+        if let Some(hash_array) = &hash_array {
+            builder = builder.with_hash_array(hash_array);
+        }
+        if let Some(border) = border {
+            builder = builder.with_mask_border(border);
+        }
+        // act
+        let robo_hash = builder.build();
+        let resolution = robo_hash.as_ref().map(|robo_hash| robo_hash.resolve());
+        let image = robo_hash.as_ref().map(|robo_hash| robo_hash.assemble());
+        // assert
+        if let Ok(Ok(image)) = image {
+            let image = image::load_from_memory(&image).unwrap();
+            prop_assert_eq!((image.width(), image.height()), size);
+            prop_assert!(matches!(resolution, Ok(Ok(_))));
+        }
+    }
+}